@@ -0,0 +1,41 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+use glam::*;
+
+/// Descriptor set of [`StandardUniforms`]. The controller's bind groups and the shader pass
+/// outputs follow it, so their sets are shifted up by one while the uniforms are enabled.
+pub const STANDARD_UNIFORMS_SET: u32 = 0;
+
+/// Uniforms maintained by the runner when enabled with `Parameters::standard_uniforms`.
+///
+/// Bound as a uniform buffer at binding 0 of the reserved set [`STANDARD_UNIFORMS_SET`]:
+///
+/// ```ignore
+/// #[spirv(uniform, descriptor_set = 0, binding = 0)] uniforms: &StandardUniforms
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::NoUninit))]
+#[repr(C)]
+pub struct StandardUniforms {
+    /// Size of the shader viewport in pixels
+    pub resolution: Vec2,
+    /// Top left corner of the shader viewport on the surface, in pixels
+    pub offset: Vec2,
+    /// Cursor position relative to the shader viewport, in pixels
+    pub cursor: Vec2,
+    /// Seconds since the first frame
+    pub time: f32,
+    /// Seconds between the previous frame and this one
+    pub delta: f32,
+    /// Index of the current frame
+    pub frame: u32,
+    /// Pressed mouse buttons, bit 0 to 4 are left, middle, right, back and forward
+    pub buttons: u32,
+    _padding: [u32; 2],
+}
+
+impl StandardUniforms {
+    pub fn is_pressed(&self, button: u32) -> bool {
+        self.buttons & (1 << button) != 0
+    }
+}
@@ -34,9 +34,10 @@ pub struct Builder<C: ControllerTrait + Send> {
 }
 
 pub enum App<C: ControllerTrait + Send> {
-    Builder(Builder<C>),
+    Builder(Box<Builder<C>>),
     Building(#[cfg(target_arch = "wasm32")] Option<PhysicalSize<u32>>),
     Graphics(Box<Graphics<C>>),
+    Failed(crate::Error),
 }
 
 impl<C: ControllerTrait + Send> App<C> {
@@ -45,11 +46,11 @@ impl<C: ControllerTrait + Send> App<C> {
         shader_bytes: Cow<'static, [u8]>,
         params: crate::Parameters<C>,
     ) -> Self {
-        Self::Builder(Builder {
+        Self::Builder(Box::new(Builder {
             event_proxy,
             shader_bytes,
             params,
-        })
+        }))
     }
 
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
@@ -63,7 +64,9 @@ impl<C: ControllerTrait + Send> App<C> {
         if size.width != 0 && size.height != 0 {
             gfx.ctx.config.width = size.width;
             gfx.ctx.config.height = size.height;
-            gfx.ctx.surface.configure(&gfx.ctx.device, &gfx.ctx.config);
+            if let Some(surface) = &gfx.ctx.surface {
+                surface.configure(&gfx.ctx.device, &gfx.ctx.config);
+            }
         }
     }
 
@@ -71,6 +74,18 @@ impl<C: ControllerTrait + Send> App<C> {
         let Self::Graphics(gfx) = self else {
             return;
         };
+        #[cfg(not(target_arch = "wasm32"))]
+        if event.state.is_pressed()
+            && !event.repeat
+            && gfx
+                .ui_state
+                .screenshot_key
+                .is_some_and(|key| event.logical_key == Key::Named(key))
+        {
+            let include_ui = gfx.ui_state.screenshot_include_ui;
+            gfx.ui_state.request_screenshot(include_ui);
+            return;
+        }
         gfx.controller.keyboard_input(event);
     }
 
@@ -78,6 +93,7 @@ impl<C: ControllerTrait + Send> App<C> {
         let Self::Graphics(gfx) = self else {
             return;
         };
+        gfx.rpass.mouse_input(state, button);
         gfx.controller.mouse_input(state, button);
     }
 
@@ -94,6 +110,7 @@ impl<C: ControllerTrait + Send> App<C> {
             return;
         };
         let position = glam::dvec2(position.x, position.y) - gfx.rpass.shader_offset().as_dvec2();
+        gfx.rpass.mouse_move(position.as_vec2());
         gfx.controller.mouse_move(position);
     }
 
@@ -121,14 +138,21 @@ impl<C: ControllerTrait + Send> App<C> {
             .and_then(|m| m.refresh_rate_millihertz().map(|x| x as f32 / 1000.0))
             .unwrap_or(60.0)
             .recip();
-        gfx.controller.update(
-            &gfx.ctx,
-            |dimensions, threads, push_constants| {
-                gfx.rpass
-                    .compute(&gfx.ctx, dimensions, threads, push_constants);
-            },
-            frame_time,
-        );
+        gfx.rpass.update(&gfx.ctx, &mut gfx.controller, frame_time);
+    }
+
+    pub fn end_frame(&mut self) {
+        let Self::Graphics(gfx) = self else {
+            return;
+        };
+        gfx.rpass.finish_gpu_profiling(&gfx.ctx);
+        gfx.ctx.clock.advance();
+        if let Some(trace) = &gfx.ctx.trace {
+            trace.end_frame();
+        }
+        // Run finished buffer map callbacks, e.g. from `GpuBuffer::read`
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = gfx.ctx.device.poll(wgpu::PollType::Poll);
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -177,6 +201,7 @@ impl<C: ControllerTrait + Send> App<C> {
             return;
         };
         gfx.rpass.new_module(&gfx.ctx, shader_path);
+        gfx.ui_state.fragment_entry_points = gfx.rpass.fragment_entry_points().to_vec();
         gfx.controller.new_shader_module();
         gfx.window.request_redraw();
     }
@@ -247,6 +272,7 @@ impl<C: ControllerTrait + Send> ApplicationHandler<CustomEvent<C>> for App<C> {
                 }
                 #[cfg(feature = "compute")]
                 self.update();
+                self.end_frame();
             }
             WindowEvent::CloseRequested => event_loop.exit(),
             WindowEvent::KeyboardInput {
@@ -275,7 +301,7 @@ impl<C: ControllerTrait + Send> ApplicationHandler<CustomEvent<C>> for App<C> {
         }
     }
 
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: CustomEvent<C>) {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: CustomEvent<C>) {
         match event {
             CustomEvent::CreateWindow(gfx) => {
                 gfx.window.request_redraw();
@@ -289,6 +315,13 @@ impl<C: ControllerTrait + Send> ApplicationHandler<CustomEvent<C>> for App<C> {
                     }
                 };
             }
+            CustomEvent::CreateWindowFailed(err) => {
+                // The event loop never returns on the web
+                #[cfg(target_arch = "wasm32")]
+                log::error!("{err}");
+                *self = Self::Failed(err);
+                event_loop.exit();
+            }
             #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
             CustomEvent::NewModule(shader_path) => self.new_module(&shader_path),
         }
@@ -296,19 +329,49 @@ impl<C: ControllerTrait + Send> ApplicationHandler<CustomEvent<C>> for App<C> {
 }
 
 async fn create_graphics<C: ControllerTrait + Send>(
-    builder: Builder<C>,
+    builder: Box<Builder<C>>,
     initial_size: PhysicalSize<u32>,
     window: Window,
 ) {
     let mut controller = builder.params.controller;
     let window = Arc::new(window);
-    let ctx = GraphicsContext::new(window.clone(), initial_size, &controller).await;
+    let mut ctx = match GraphicsContext::new(Some(window.clone()), initial_size, &controller).await
+    {
+        Ok(ctx) => ctx,
+        Err(err) => {
+            builder
+                .event_proxy
+                .send_event(CustomEvent::CreateWindowFailed(err))
+                .ok();
+            return;
+        }
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let trace = builder.params.options.trace.clone();
+    #[cfg(target_arch = "wasm32")]
+    let trace = None;
+    ctx.trace = crate::trace::Trace::new(trace);
 
     let ui = Ui::new(window.clone());
 
-    let ui_state = UiState::new(builder.params.options);
+    let rpass = match RenderPass::new(
+        &ctx,
+        &builder.shader_bytes,
+        &mut controller,
+        &builder.params.options,
+    ) {
+        Ok(rpass) => rpass,
+        Err(err) => {
+            builder
+                .event_proxy
+                .send_event(CustomEvent::CreateWindowFailed(err))
+                .ok();
+            return;
+        }
+    };
 
-    let rpass = RenderPass::new(&ctx, &builder.shader_bytes, &mut controller);
+    let mut ui_state = UiState::new(builder.params.options);
+    ui_state.fragment_entry_points = rpass.fragment_entry_points().to_vec();
 
     let gfx = Graphics {
         rpass,
@@ -0,0 +1,94 @@
+use crate::{Error, context::GraphicsContext};
+use glam::UVec2;
+use std::path::Path;
+
+/// Copy a region of `texture` back to the CPU as tightly packed RGBA8 pixels.
+///
+/// Blocks until the GPU has finished. `texture` must have been created with `COPY_SRC`.
+pub(crate) fn read_texture(
+    ctx: &GraphicsContext,
+    texture: &wgpu::Texture,
+    origin: UVec2,
+    size: UVec2,
+) -> Result<Vec<u8>, Error> {
+    let bgra = match texture.format() {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        format => return Err(Error::UnsupportedCaptureFormat(format)),
+    };
+    let unpadded_bytes_per_row = size.x * 4;
+    let padded_bytes_per_row =
+        unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Capture Readback Buffer"),
+        size: (padded_bytes_per_row * size.y) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = ctx
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder"),
+        });
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: origin.x,
+                y: origin.y,
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+    );
+    ctx.queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    ctx.device.poll(wgpu::PollType::wait_indefinitely())?;
+    // The callback has run once the poll returned, a missing result means it was dropped
+    receiver.recv().map_err(|_| wgpu::BufferAsyncError)??;
+
+    let mut data = Vec::with_capacity((unpadded_bytes_per_row * size.y) as usize);
+    for row in slice
+        .get_mapped_range()
+        .chunks_exact(padded_bytes_per_row as usize)
+    {
+        data.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+    }
+    if bgra {
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    Ok(data)
+}
+
+/// Write tightly packed RGBA8 pixels to a PNG file
+pub fn save_png(path: impl AsRef<Path>, size: UVec2, rgba: &[u8]) -> Result<(), Error> {
+    let file = std::fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), size.x, size.y);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    Ok(writer.finish()?)
+}
@@ -0,0 +1,102 @@
+use web_time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug)]
+enum Source {
+    Wall(Instant),
+    Fixed(Duration),
+}
+
+/// Frame clock shared by the runner and controllers.
+///
+/// Controllers should read time from here rather than `Instant::now()` so that
+/// offline rendering with a fixed timestep is deterministic.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    source: Source,
+    frame: u64,
+    elapsed: Duration,
+    delta: Duration,
+}
+
+impl Clock {
+    /// Follows wall-clock time
+    pub fn wall() -> Self {
+        Self {
+            source: Source::Wall(Instant::now()),
+            frame: 0,
+            elapsed: Duration::ZERO,
+            delta: Duration::ZERO,
+        }
+    }
+
+    /// Advances by exactly `delta` every frame
+    pub fn fixed(delta: Duration) -> Self {
+        Self {
+            source: Source::Fixed(delta),
+            frame: 0,
+            elapsed: Duration::ZERO,
+            delta,
+        }
+    }
+
+    /// Index of the current frame
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Time since the first frame
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Time between the previous frame and this one
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        matches!(self.source, Source::Fixed(_))
+    }
+
+    /// Called by the runner once a frame is finished
+    pub(crate) fn advance(&mut self) {
+        self.frame += 1;
+        match self.source {
+            Source::Wall(start) => {
+                let elapsed = start.elapsed();
+                self.delta = elapsed - self.elapsed;
+                self.elapsed = elapsed;
+            }
+            Source::Fixed(delta) => self.elapsed += delta,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_clock_advances_by_delta() {
+        let delta = Duration::from_secs(1) / 60;
+        let mut clock = Clock::fixed(delta);
+        assert!(clock.is_fixed());
+        assert_eq!(clock.elapsed(), Duration::ZERO);
+        for frame in 1..=600 {
+            clock.advance();
+            assert_eq!(clock.frame(), frame);
+            assert_eq!(clock.delta(), delta);
+            assert_eq!(clock.elapsed(), delta * frame as u32);
+        }
+    }
+
+    #[test]
+    fn wall_clock_starts_at_zero() {
+        let mut clock = Clock::wall();
+        assert!(!clock.is_fixed());
+        assert_eq!((clock.frame(), clock.elapsed()), (0, Duration::ZERO));
+        clock.advance();
+        assert_eq!(clock.frame(), 1);
+        assert_eq!(clock.delta(), clock.elapsed());
+    }
+}
@@ -0,0 +1,71 @@
+use glam::UVec2;
+
+/// Color attachment owned by the runner, the size of the surface.
+///
+/// Used as the multisampled target that is resolved into the actual output, and to keep
+/// the final pass's previous contents when it loads instead of clearing.
+pub(crate) struct ColorTarget {
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    size: UVec2,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl ColorTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        size: UVec2,
+    ) -> Self {
+        let texture = create_texture(device, format, sample_count, size);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            format,
+            sample_count,
+            size,
+            texture,
+            view,
+        }
+    }
+
+    /// Recreate the texture if the surface size changed. Previous contents are lost.
+    pub fn resize(&mut self, device: &wgpu::Device, size: UVec2) {
+        if size != self.size {
+            *self = Self::new(device, self.format, self.sample_count, size);
+        }
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    size: UVec2,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("color target"),
+        size: wgpu::Extent3d {
+            width: size.x.max(1),
+            height: size.y.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
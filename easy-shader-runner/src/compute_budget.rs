@@ -0,0 +1,130 @@
+use crate::gpu_profiler::TimestampQueries;
+
+/// How much compute work fits in the current frame, passed to [`crate::ControllerTrait::update`]
+#[derive(Clone, Copy, Debug)]
+pub struct ComputeBudget {
+    /// Duration of the whole frame in seconds, shared by the compute work and rendering
+    pub allowed_duration: f32,
+    /// Average GPU time of one dispatch in seconds, measured with timestamp queries.
+    /// `None` if the adapter lacks `TIMESTAMP_QUERY` or nothing has been measured yet.
+    pub gpu_time_per_dispatch: Option<f32>,
+}
+
+impl ComputeBudget {
+    /// Share of `allowed_duration` that [`Self::recommended_dispatches`] fills, leaving the
+    /// rest of the frame to the shader and egui passes
+    pub const COMPUTE_SHARE: f32 = 0.5;
+
+    /// How many dispatches are expected to fit in [`Self::COMPUTE_SHARE`] of
+    /// `allowed_duration`, if GPU timings are available. Always at least 1.
+    pub fn recommended_dispatches(&self) -> Option<u32> {
+        self.gpu_time_per_dispatch.map(|time| {
+            (Self::COMPUTE_SHARE * self.allowed_duration / time)
+                .floor()
+                .clamp(1.0, u32::MAX as f32) as u32
+        })
+    }
+}
+
+/// Weight of the newest measurement in the moving average
+const SMOOTHING: f32 = 0.2;
+
+/// Measures the GPU time of all dispatches in an update with timestamp queries.
+///
+/// Results are read back without blocking and arrive a few frames later.
+pub(crate) struct ComputeTimer {
+    /// Pending measurements hold their number of dispatches
+    queries: TimestampQueries<u32>,
+    gpu_time_per_dispatch: Option<f32>,
+}
+
+impl ComputeTimer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        Some(Self {
+            queries: TimestampQueries::new(device, queue, "compute timer", 2)?,
+            gpu_time_per_dispatch: None,
+        })
+    }
+
+    pub fn gpu_time_per_dispatch(&self) -> Option<f32> {
+        self.gpu_time_per_dispatch
+    }
+
+    /// Fold in a finished measurement, if any
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some((dispatches, timestamps)) = self.queries.poll(device) else {
+            return;
+        };
+        let time = ((timestamps[1] - timestamps[0]).max(0.0) * 1e-9) as f32 / dispatches as f32;
+        self.gpu_time_per_dispatch = Some(match self.gpu_time_per_dispatch {
+            Some(average) => average + SMOOTHING * (time - average),
+            None => time,
+        });
+    }
+
+    /// Whether a new measurement can be started
+    pub fn is_idle(&self) -> bool {
+        self.queries.is_idle()
+    }
+
+    /// Record the start timestamp
+    pub fn begin(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.queries.write(encoder, 0);
+    }
+
+    /// Record the end timestamp and copy both for readback
+    pub fn end(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.queries.write(encoder, 1);
+        self.queries.resolve(encoder, 0..2);
+        self.queries.copy_for_readback(encoder);
+    }
+
+    /// Start reading back a measurement of `dispatches` dispatches once `end` was submitted
+    pub fn read_back(&mut self, dispatches: u32) {
+        self.queries.read_back(dispatches);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(allowed_duration: f32, gpu_time_per_dispatch: Option<f32>) -> ComputeBudget {
+        ComputeBudget {
+            allowed_duration,
+            gpu_time_per_dispatch,
+        }
+    }
+
+    #[test]
+    fn leaves_time_for_rendering() {
+        // Half of a 1/64 s frame fits 8 dispatches of 1/1024 s
+        assert_eq!(
+            budget(1.0 / 64.0, Some(1.0 / 1024.0)).recommended_dispatches(),
+            Some(8)
+        );
+        assert_eq!(budget(0.016, Some(0.003)).recommended_dispatches(), Some(2));
+    }
+
+    #[test]
+    fn recommends_at_least_one_dispatch() {
+        assert_eq!(budget(0.016, Some(0.1)).recommended_dispatches(), Some(1));
+        assert_eq!(
+            budget(0.016, Some(f32::INFINITY)).recommended_dispatches(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn unbounded_without_a_deadline() {
+        assert_eq!(
+            budget(f32::INFINITY, Some(0.001)).recommended_dispatches(),
+            Some(u32::MAX)
+        );
+    }
+
+    #[test]
+    fn needs_measurements() {
+        assert_eq!(budget(0.016, None).recommended_dispatches(), None);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::Error;
+use crate::spirv::{EXECUTION_MODEL_GL_COMPUTE, SpirvModule};
+use glam::{UVec2, UVec3};
+
+/// How many workgroups a dispatch runs.
+///
+/// Counts known on the CPU are checked against `max_compute_workgroups_per_dimension`.
+/// Invalid dispatches are logged and skipped.
+#[derive(Clone, Copy, Debug)]
+pub enum Dispatch<'a> {
+    /// Exactly this many workgroups
+    Workgroups(UVec3),
+    /// Enough workgroups to cover `dimensions`, using the `threads` the kernel declares in
+    /// the shader
+    Dimensions(UVec3),
+    /// Enough workgroups of `threads`, as declared in the compute shader, to cover `dimensions`
+    Threads { dimensions: UVec3, threads: UVec3 },
+    /// Workgroup counts read from a buffer when the dispatch runs, e.g. written by a previous
+    /// kernel. The buffer needs `INDIRECT` usage and holds a [`wgpu::util::DispatchIndirectArgs`]
+    /// at `offset`.
+    Indirect {
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+    },
+}
+
+impl Dispatch<'_> {
+    /// `count` workgroups along x
+    pub fn workgroups_1d(count: u32) -> Self {
+        Self::Workgroups(UVec3::new(count, 1, 1))
+    }
+
+    /// Cover `len` invocations along x
+    pub fn dimensions_1d(len: u32) -> Self {
+        Self::Dimensions(UVec3::new(len, 1, 1))
+    }
+
+    /// Cover `size` invocations along x and y
+    pub fn dimensions_2d(size: UVec2) -> Self {
+        Self::Dimensions(size.extend(1))
+    }
+}
+
+/// A [`Dispatch`] with its workgroup counts resolved
+pub(crate) enum Workgroups<'a> {
+    Count(UVec3),
+    Indirect {
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+    },
+}
+
+impl<'a> Dispatch<'a> {
+    /// Resolve the workgroup counts known on the CPU and check them against `limit`.
+    /// `threads` are the ones declared by `entry_point`, if they could be read from the shader.
+    pub(crate) fn workgroups(
+        self,
+        entry_point: &str,
+        threads: Option<UVec3>,
+        limit: u32,
+    ) -> Result<Workgroups<'a>, Error> {
+        let (dimensions, threads) = match self {
+            Self::Workgroups(count) => (count, UVec3::ONE),
+            Self::Dimensions(dimensions) => (
+                dimensions,
+                threads.ok_or_else(|| Error::UnknownWorkgroupSize(entry_point.to_owned()))?,
+            ),
+            Self::Threads {
+                dimensions,
+                threads,
+            } => (dimensions, threads),
+            Self::Indirect { buffer, offset } => {
+                return Ok(Workgroups::Indirect { buffer, offset });
+            }
+        };
+        let count = div_ceil(dimensions, threads).ok_or_else(|| Error::ZeroThreads {
+            entry_point: entry_point.to_owned(),
+            threads,
+        })?;
+        if count.max_element() > limit {
+            return Err(Error::TooManyWorkgroups {
+                entry_point: entry_point.to_owned(),
+                count,
+                limit,
+            });
+        }
+        Ok(Workgroups::Count(count))
+    }
+}
+
+/// Workgroups of `threads` needed to cover `dimensions`, `None` if a thread count is zero
+fn div_ceil(dimensions: UVec3, threads: UVec3) -> Option<UVec3> {
+    (threads.min_element() > 0).then(|| {
+        UVec3::new(
+            dimensions.x.div_ceil(threads.x),
+            dimensions.y.div_ceil(threads.y),
+            dimensions.z.div_ceil(threads.z),
+        )
+    })
+}
+
+/// A compute shader entry point with its own push constants
+#[derive(Clone, Debug)]
+pub struct ComputeKernel {
+    pub entry_point: String,
+    /// Size in bytes of the push constants, rounded up to a multiple of 4
+    pub push_constant_size: u32,
+}
+
+impl ComputeKernel {
+    /// Kernel whose push constants are a `T`
+    pub fn new<T: bytemuck::NoUninit>(entry_point: impl Into<String>) -> Self {
+        Self::with_push_constant_size(entry_point, std::mem::size_of::<T>() as u32)
+    }
+
+    pub fn with_push_constant_size(entry_point: impl Into<String>, size: u32) -> Self {
+        Self {
+            entry_point: entry_point.into(),
+            push_constant_size: size.next_multiple_of(wgpu::PUSH_CONSTANT_ALIGNMENT),
+        }
+    }
+}
+
+/// Read the `threads` declared for `entry_point` from a SPIR-V module
+pub(crate) fn reflect_workgroup_size(spirv: &[u8], entry_point: &str) -> Option<UVec3> {
+    const OP_EXECUTION_MODE: u32 = 16;
+    const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+    let module = SpirvModule::parse(spirv)?;
+    let id = module.entry_point(EXECUTION_MODEL_GL_COMPUTE, entry_point)?;
+    module
+        .instructions()
+        .find_map(|(opcode, operands)| match operands {
+            [target, EXECUTION_MODE_LOCAL_SIZE, x, y, z]
+                if opcode == OP_EXECUTION_MODE && *target == id =>
+            {
+                Some(UVec3::new(*x, *y, *z))
+            }
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spirv::tests::module;
+
+    #[test]
+    fn reflects_declared_threads() {
+        assert_eq!(
+            reflect_workgroup_size(&module(), "main"),
+            Some(UVec3::new(16, 8, 1))
+        );
+    }
+
+    #[test]
+    fn reflects_nothing_for_other_entry_points() {
+        assert_eq!(reflect_workgroup_size(&module(), "other"), None);
+        assert_eq!(reflect_workgroup_size(&module(), "frag"), None);
+    }
+
+    #[test]
+    fn div_ceil_covers_dimensions() {
+        assert_eq!(
+            div_ceil(UVec3::new(10, 8, 1), UVec3::new(4, 8, 1)),
+            Some(UVec3::new(3, 1, 1))
+        );
+        assert_eq!(
+            div_ceil(UVec3::ZERO, UVec3::new(64, 1, 1)),
+            Some(UVec3::ZERO)
+        );
+    }
+
+    #[test]
+    fn div_ceil_rejects_zero_threads() {
+        assert_eq!(div_ceil(UVec3::new(10, 8, 1), UVec3::new(4, 0, 1)), None);
+    }
+
+    #[test]
+    fn workgroups_are_checked_against_the_limit() {
+        let dispatch = Dispatch::Threads {
+            dimensions: UVec3::new(1024, 1, 1),
+            threads: UVec3::new(4, 1, 1),
+        };
+        assert!(matches!(
+            dispatch.workgroups("main", None, 256),
+            Ok(Workgroups::Count(count)) if count == UVec3::new(256, 1, 1)
+        ));
+        assert!(matches!(
+            dispatch.workgroups("main", None, 255),
+            Err(Error::TooManyWorkgroups { .. })
+        ));
+        assert!(matches!(
+            Dispatch::dimensions_1d(64).workgroups("main", None, 256),
+            Err(Error::UnknownWorkgroupSize(_))
+        ));
+        assert!(matches!(
+            Dispatch::Threads {
+                dimensions: UVec3::ONE,
+                threads: UVec3::ZERO,
+            }
+            .workgroups("main", None, 256),
+            Err(Error::ZeroThreads { .. })
+        ));
+    }
+}
@@ -1,20 +1,38 @@
-use crate::controller::ControllerTrait;
+use crate::{
+    Error,
+    clock::Clock,
+    controller::ControllerTrait,
+    trace::{Trace, TraceSpan},
+};
 use egui_winit::winit::{dpi::PhysicalSize, window::Window};
 use std::sync::Arc;
 
 pub struct GraphicsContext {
-    pub surface: wgpu::Surface<'static>,
+    /// `None` when rendering headless
+    pub surface: Option<wgpu::Surface<'static>>,
+    pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
+    /// Use this instead of wall-clock time so offline rendering is deterministic
+    pub clock: Clock,
+    /// See [`crate::Parameters::trace`]
+    pub(crate) trace: Option<Trace>,
 }
 
 impl GraphicsContext {
+    /// Texture format used for offscreen rendering when there is no window
+    pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    /// Create a graphics context.
+    ///
+    /// If `window` is `None` no surface is created and `config` describes an offscreen target
+    /// of [`Self::HEADLESS_FORMAT`] instead.
     pub async fn new<C: ControllerTrait>(
-        window: Arc<Window>,
+        window: Option<Arc<Window>>,
         initial_size: PhysicalSize<u32>,
         controller: &C,
-    ) -> GraphicsContext {
+    ) -> Result<GraphicsContext, Error> {
         let instance = wgpu::Instance::new(
             &wgpu::InstanceDescriptor {
                 backends: wgpu::Backends::PRIMARY,
@@ -25,46 +43,70 @@ impl GraphicsContext {
             .with_env(),
         );
 
-        #[cfg(target_arch = "wasm32")]
-        let canvas = {
-            use egui_winit::winit::platform::web::*;
-            window.canvas().unwrap()
-        };
-        let initial_surface = instance.create_surface(window);
-        #[cfg(target_arch = "wasm32")]
-        if initial_surface.is_err() {
-            web_sys::window()
-                .and_then(|win| win.document())
-                .and_then(|doc| {
-                    doc.body().and_then(|body| {
-                        let element = doc.create_element("span").unwrap();
-                        element.set_inner_html("Your browser does not support WebGPU");
-                        element.set_id("incompatible_no_webgpu");
-                        body.replace_child(&element.into(), &canvas.into()).ok()
+        let initial_surface = window.map(|window| {
+            #[cfg(target_arch = "wasm32")]
+            let canvas = {
+                use egui_winit::winit::platform::web::*;
+                window.canvas().unwrap()
+            };
+            let surface = instance.create_surface(window);
+            #[cfg(target_arch = "wasm32")]
+            if surface.is_err() {
+                web_sys::window()
+                    .and_then(|win| win.document())
+                    .and_then(|doc| {
+                        doc.body().and_then(|body| {
+                            let element = doc.create_element("span").unwrap();
+                            element.set_inner_html("Your browser does not support WebGPU");
+                            element.set_id("incompatible_no_webgpu");
+                            body.replace_child(&element.into(), &canvas.into()).ok()
+                        })
                     })
-                })
-                .expect("couldn't append message to document body");
-        }
-        let initial_surface = initial_surface.expect("Failed to create surface from window");
+                    .expect("couldn't append message to document body");
+            }
+            surface.expect("Failed to create surface from window")
+        });
 
-        let adapter = instance
+        let adapter = match instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&initial_surface),
+                compatible_surface: initial_surface.as_ref(),
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap();
+        {
+            Ok(adapter) => adapter,
+            // Without a surface there may only be a software adapter (e.g. lavapipe or llvmpipe)
+            Err(_) if initial_surface.is_none() => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    compatible_surface: None,
+                    force_fallback_adapter: true,
+                })
+                .await
+                .map_err(Error::NoAdapter)?,
+            Err(err) => return Err(Error::NoAdapter(err)),
+        };
 
         let (features, limits) =
             controller.describe_wgpu_features_and_limits(adapter.features(), adapter.limits());
+        // Used to measure GPU time, for multisampling beyond 4x and to cache pipelines where
+        // available
+        let features = features
+            | (adapter.features()
+                & (wgpu::Features::TIMESTAMP_QUERY
+                    | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                    | wgpu::Features::PIPELINE_CACHE));
         let (features, limits) = if cfg!(feature = "emulate_constants") {
             (features, limits)
         } else {
             (
                 features | wgpu::Features::PUSH_CONSTANTS,
                 wgpu::Limits {
-                    max_push_constant_size: limits.max_push_constant_size.max(128),
+                    // Whatever the controller's constants need is checked against this later
+                    max_push_constant_size: limits
+                        .max_push_constant_size
+                        .max(adapter.limits().max_push_constant_size),
                     ..limits
                 },
             )
@@ -76,8 +118,7 @@ impl GraphicsContext {
                 required_limits: limits,
                 ..Default::default()
             })
-            .await
-            .expect("Failed to create device");
+            .await?;
 
         fn auto_configure_surface<'a>(
             adapter: &wgpu::Adapter,
@@ -94,20 +135,70 @@ impl GraphicsContext {
                     )
                 });
             surface_config.present_mode = wgpu::PresentMode::AutoVsync;
+            // Allows screenshots to read back the surface, and copying accumulated frames to it
+            surface_config.usage |= capabilities.usages
+                & (wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST);
             surface_config.format =
                 egui_wgpu::preferred_framebuffer_format(&capabilities.formats).unwrap();
             surface.configure(device, &surface_config);
             (surface, surface_config)
         }
 
-        let (surface, config) =
-            auto_configure_surface(&adapter, &device, initial_surface, initial_size);
+        let (surface, config) = match initial_surface {
+            Some(surface) => {
+                let (surface, config) =
+                    auto_configure_surface(&adapter, &device, surface, initial_size);
+                (Some(surface), config)
+            }
+            None => (
+                None,
+                wgpu::SurfaceConfiguration {
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::COPY_SRC
+                        | wgpu::TextureUsages::COPY_DST,
+                    format: Self::HEADLESS_FORMAT,
+                    width: initial_size.width,
+                    height: initial_size.height,
+                    present_mode: wgpu::PresentMode::AutoVsync,
+                    desired_maximum_frame_latency: 2,
+                    alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                    view_formats: vec![],
+                },
+            ),
+        };
 
-        GraphicsContext {
+        Ok(GraphicsContext {
             surface,
+            adapter,
             device,
             queue,
             config,
+            clock: Clock::wall(),
+            trace: None,
+        })
+    }
+
+    /// CPU span of the trace being recorded, if any. Ends when dropped.
+    pub(crate) fn trace_span(&self, name: &'static str) -> Option<TraceSpan<'_>> {
+        self.trace
+            .as_ref()
+            .filter(|trace| trace.is_recording())
+            .map(|trace| trace.span(name))
+    }
+
+    /// What `format` supports on this device, including multisample counts
+    pub fn texture_format_features(
+        &self,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::TextureFormatFeatures {
+        if self
+            .device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+        {
+            self.adapter.get_texture_format_features(format)
+        } else {
+            format.guaranteed_format_features(self.device.features())
         }
     }
 
@@ -120,7 +211,9 @@ impl GraphicsContext {
         };
         if self.config.present_mode != present_mode {
             self.config.present_mode = present_mode;
-            self.surface.configure(&self.device, &self.config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
         }
     }
 }
@@ -1,8 +1,19 @@
-use crate::GraphicsContext;
+#[cfg(feature = "compute")]
+use crate::{ComputeBudget, ComputeKernel, Dispatch};
+use crate::{GraphicsContext, IndexBuffer, ShaderPass, VertexBuffer};
 use egui_winit::winit::event::{ElementState, KeyEvent, MouseButton, TouchPhase};
 use glam::*;
 
 pub trait ControllerTrait: 'static {
+    /// Push constants of the render pipelines, returned by `prepare_render`.
+    /// Their size is checked against the device limit at startup.
+    type PushConstants: bytemuck::NoUninit;
+
+    /// Push constants of the `EntryPoints::compute` kernel used when
+    /// `describe_compute_kernels` is empty. Unused otherwise, e.g. `()`.
+    #[cfg(feature = "compute")]
+    type ComputeConstants: bytemuck::NoUninit;
+
     fn resize(&mut self, _size: UVec2);
 
     fn touch(&mut self, _id: u64, _phase: TouchPhase, _position: DVec2) {}
@@ -15,25 +26,30 @@ pub trait ControllerTrait: 'static {
 
     fn keyboard_input(&mut self, _key: KeyEvent) {}
 
-    fn prepare_render(
-        &mut self,
-        gfx_ctx: &GraphicsContext,
-        offset: Vec2,
-    ) -> impl bytemuck::NoUninit;
+    /// Push constants of the render pipelines, called every frame.
+    /// They are shared by the vertex and fragment stages.
+    fn prepare_render(&mut self, gfx_ctx: &GraphicsContext, offset: Vec2) -> Self::PushConstants;
+
+    /// Compute kernels that `update` can dispatch, by entry point.
+    /// If empty, the single kernel `EntryPoints::compute` is used with `Self::ComputeConstants`.
+    #[cfg(feature = "compute")]
+    fn describe_compute_kernels(&mut self, _gfx_ctx: &GraphicsContext) -> Vec<ComputeKernel> {
+        vec![]
+    }
 
     /// Run the compute shader after rendering
     #[cfg(feature = "compute")]
     fn update<
         F: Fn(
-            UVec3, // dimensions
-            UVec3, // threads (same as declared in compute shader)
-            &[u8], // push_constants
+            &str,         // kernel (entry point of one of `describe_compute_kernels`)
+            Dispatch<'_>, // workgroups
+            &[u8],        // push_constants
         ),
     >(
         &mut self,
         _gfx_ctx: &GraphicsContext,
         _compute: F,
-        _allowed_duration: f32,
+        _budget: ComputeBudget,
     ) {
     }
 
@@ -44,6 +60,44 @@ pub trait ControllerTrait: 'static {
         (vec![], vec![])
     }
 
+    /// Whether `describe_bind_groups` is called again after every `resize`, for resources
+    /// that depend on the size of the viewport
+    fn rebuild_bind_groups_on_resize(&self) -> bool {
+        false
+    }
+
+    /// Called every frame.
+    /// return Whether `describe_bind_groups` should be called again before rendering.
+    /// Returning the same layouts as before avoids recreating the pipelines.
+    fn bind_groups_outdated(&mut self) -> bool {
+        false
+    }
+
+    /// Offscreen passes rendered in order before the final pass, see [`ShaderPass`]
+    fn describe_shader_passes(&mut self, _gfx_ctx: &GraphicsContext) -> Vec<ShaderPass> {
+        vec![]
+    }
+
+    /// Depth (and stencil) state of all render pipelines.
+    /// If `Some`, a depth attachment of its format the size of the surface is cleared for every pass.
+    fn describe_depth_stencil(
+        &mut self,
+        _gfx_ctx: &GraphicsContext,
+    ) -> Option<wgpu::DepthStencilState> {
+        None
+    }
+
+    /// Topology, strip index format, cull mode and polygon mode of all render pipelines.
+    /// Line and point polygon modes need their wgpu features enabled.
+    fn describe_primitive_state(&mut self, _gfx_ctx: &GraphicsContext) -> wgpu::PrimitiveState {
+        wgpu::PrimitiveState::default()
+    }
+
+    /// Blend state of the final pass, offscreen [`ShaderPass`]es are never blended
+    fn describe_blend_state(&mut self, _gfx_ctx: &GraphicsContext) -> Option<wgpu::BlendState> {
+        None
+    }
+
     fn describe_vertex_buffer_layouts(
         &mut self,
         _gfx_ctx: &GraphicsContext,
@@ -52,14 +106,49 @@ pub trait ControllerTrait: 'static {
     }
 
     /// Called every frame.
-    /// return Vertex buffer and number of vertices as well as Index buffer and number of indices
+    /// return Vertex buffers with their slots and number of vertices as well as Index buffer
     #[allow(clippy::type_complexity)]
     fn get_vertex_index_buffer(
         &self,
-    ) -> (Option<(&wgpu::Buffer, u32)>, Option<(&wgpu::Buffer, u32)>) {
+    ) -> (
+        Option<(Vec<VertexBuffer<'_>>, u32)>,
+        Option<IndexBuffer<'_>>,
+    ) {
         (None, None)
     }
 
+    /// Called every frame.
+    /// return Number of vertices drawn when there is no vertex buffer, and number of instances.
+    /// Procedural geometry can be built from `vertex_index` and `instance_index`.
+    fn get_vertex_instance_count(&self) -> (u32, u32) {
+        (3, 1)
+    }
+
+    /// Called every frame.
+    /// return Instance-rate vertex buffers with their slots, the number of instances is the
+    /// one of `get_vertex_instance_count`
+    fn get_instance_buffers(&self) -> Vec<VertexBuffer<'_>> {
+        vec![]
+    }
+
+    /// Called every frame.
+    /// return Buffer and offset of the draw arguments, replacing the counts above so they can
+    /// be written on the GPU, e.g. by a culling kernel. The buffer needs `INDIRECT` usage and
+    /// holds a [`wgpu::util::DrawIndexedIndirectArgs`] if there is an index buffer, otherwise a
+    /// [`wgpu::util::DrawIndirectArgs`]. Applies to the shader passes too.
+    fn get_indirect_buffer(&self) -> Option<(&wgpu::Buffer, wgpu::BufferAddress)> {
+        None
+    }
+
+    /// Called every frame.
+    /// return How the final pass starts: cleared to a color, or loaded to keep drawing over
+    /// the previous frame, e.g. to accumulate additively with [`Self::describe_blend_state`].
+    /// Loading needs multisampling or a surface that supports `COPY_DST`, otherwise the
+    /// surface texture is loaded directly and may hold an older frame.
+    fn get_load_op(&self) -> wgpu::LoadOp<wgpu::Color> {
+        wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+    }
+
     fn describe_wgpu_features_and_limits(
         &self,
         _supported_features: wgpu::Features,
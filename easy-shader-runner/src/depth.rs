@@ -0,0 +1,97 @@
+use glam::UVec2;
+
+/// Depth attachment shared by the shader passes, the final pass and the egui pass.
+///
+/// Cleared at the start of every pass and recreated when the surface size changes. The egui
+/// pass is never multisampled, so it gets a single-sampled texture of its own when the
+/// other passes are.
+pub(crate) struct DepthTarget {
+    pub state: wgpu::DepthStencilState,
+    sample_count: u32,
+    size: UVec2,
+    view: wgpu::TextureView,
+    ui_view: Option<wgpu::TextureView>,
+}
+
+impl DepthTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        state: wgpu::DepthStencilState,
+        sample_count: u32,
+        size: UVec2,
+    ) -> Self {
+        let view = create_view(device, state.format, sample_count, size);
+        let ui_view = (sample_count > 1).then(|| create_view(device, state.format, 1, size));
+        Self {
+            state,
+            sample_count,
+            size,
+            view,
+            ui_view,
+        }
+    }
+
+    /// Recreate the texture if the surface size changed
+    pub fn resize(&mut self, device: &wgpu::Device, size: UVec2) {
+        if size != self.size {
+            self.view = create_view(device, self.state.format, self.sample_count, size);
+            if self.ui_view.is_some() {
+                self.ui_view = Some(create_view(device, self.state.format, 1, size));
+            }
+            self.size = size;
+        }
+    }
+
+    pub fn attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        attachment(&self.view, self.state.format)
+    }
+
+    /// Single-sampled attachment for the egui pass
+    pub fn ui_attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        attachment(
+            self.ui_view.as_ref().unwrap_or(&self.view),
+            self.state.format,
+        )
+    }
+}
+
+fn attachment(
+    view: &wgpu::TextureView,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+    wgpu::RenderPassDepthStencilAttachment {
+        view,
+        depth_ops: format.has_depth_aspect().then_some(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(1.0),
+            store: wgpu::StoreOp::Store,
+        }),
+        stencil_ops: format.has_stencil_aspect().then_some(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(0),
+            store: wgpu::StoreOp::Store,
+        }),
+    }
+}
+
+fn create_view(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    size: UVec2,
+) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth target"),
+            size: wgpu::Extent3d {
+                width: size.x.max(1),
+                height: size.y.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
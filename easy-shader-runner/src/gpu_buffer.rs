@@ -0,0 +1,149 @@
+use crate::GraphicsContext;
+use bytemuck::Pod;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+type ReadSlot = Arc<Mutex<Option<Result<Vec<u8>, wgpu::BufferAsyncError>>>>;
+
+/// A storage buffer of `T`s whose contents can be read back without stalling rendering.
+///
+/// Readbacks complete on a later frame, either through a callback ([`Self::read`]) or by
+/// polling ([`Self::request_read`] then [`Self::try_read`]). Works natively and on wasm.
+pub struct GpuBuffer<T: Pod> {
+    buffer: wgpu::Buffer,
+    len: usize,
+    /// Result of the last [`Self::request_read`], `None` while still in flight
+    polled: Option<ReadSlot>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> GpuBuffer<T> {
+    /// `COPY_SRC` and `COPY_DST` are always added to `usage`
+    pub fn new(
+        device: &wgpu::Device,
+        label: Option<&str>,
+        contents: &[T],
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        use wgpu::util::DeviceExt;
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: bytemuck::cast_slice(contents),
+            usage: usage | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+        Self {
+            buffer,
+            len: contents.len(),
+            polled: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Buffer of `len` zeroed elements
+    pub fn zeroed(
+        device: &wgpu::Device,
+        label: Option<&str>,
+        len: usize,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        Self::new(device, label, &vec![T::zeroed(); len], usage)
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Overwrite the start of the buffer
+    pub fn write(&self, queue: &wgpu::Queue, data: &[T]) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(data));
+    }
+
+    /// Copy the current contents back and pass them to `callback` once the GPU is done.
+    ///
+    /// The copy is ordered after all work submitted so far.
+    pub fn read(
+        &self,
+        ctx: &GraphicsContext,
+        callback: impl FnOnce(Vec<T>) + wgpu::WasmNotSend + 'static,
+    ) {
+        self.read_bytes(ctx, move |result| {
+            if let Ok(bytes) = result {
+                callback(cast_vec(&bytes));
+            }
+        });
+    }
+
+    /// Start a readback to be collected with [`Self::try_read`].
+    ///
+    /// Does nothing if the previous readback has not been collected yet.
+    pub fn request_read(&mut self, ctx: &GraphicsContext) {
+        if self.polled.is_some() {
+            return;
+        }
+        let slot = Arc::new(Mutex::new(None));
+        self.polled = Some(slot.clone());
+        self.read_bytes(ctx, move |result| *slot.lock().unwrap() = Some(result));
+    }
+
+    /// Result of [`Self::request_read`] if it has arrived. Never blocks.
+    ///
+    /// A failed readback also returns `None` once it is done, so a new one can be requested.
+    pub fn try_read(
+        &mut self,
+        #[cfg_attr(target_arch = "wasm32", allow(unused_variables))] ctx: &GraphicsContext,
+    ) -> Option<Vec<T>> {
+        // On wasm map callbacks run from the browser's event loop instead
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = ctx.device.poll(wgpu::PollType::Poll);
+        let result = self.polled.as_ref()?.lock().unwrap().take()?;
+        self.polled = None;
+        result.ok().map(|bytes| cast_vec(&bytes))
+    }
+
+    fn read_bytes(
+        &self,
+        ctx: &GraphicsContext,
+        callback: impl FnOnce(Result<Vec<u8>, wgpu::BufferAsyncError>) + wgpu::WasmNotSend + 'static,
+    ) {
+        let staging = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GpuBuffer readback"),
+            size: self.buffer.size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("GpuBuffer readback encoder"),
+            });
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &staging, 0, self.buffer.size());
+        ctx.queue.submit(Some(encoder.finish()));
+
+        let len = self.len * std::mem::size_of::<T>();
+        let mapped = staging.clone();
+        staging.map_async(wgpu::MapMode::Read, .., move |result| {
+            if let Err(err) = result {
+                log::error!("Failed to read back buffer: {err}");
+                callback(Err(err));
+                return;
+            }
+            let bytes = mapped.slice(..).get_mapped_range()[..len].to_vec();
+            mapped.unmap();
+            callback(Ok(bytes));
+        });
+    }
+}
+
+fn cast_vec<T: Pod>(bytes: &[u8]) -> Vec<T> {
+    let mut data = vec![T::zeroed(); bytes.len() / std::mem::size_of::<T>().max(1)];
+    bytemuck::cast_slice_mut(&mut data).copy_from_slice(bytes);
+    data
+}
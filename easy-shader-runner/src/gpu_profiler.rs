@@ -0,0 +1,352 @@
+use std::ops::Range;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU8, Ordering},
+};
+use web_time::Instant;
+
+/// Smoothed GPU time of each part of a frame in seconds, `None` if not measured yet
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuTimings {
+    /// All dispatches of [`crate::ControllerTrait::update`]
+    pub compute: Option<f32>,
+    /// The shader passes and the final pass
+    pub shader: Option<f32>,
+    /// The egui pass
+    pub ui: Option<f32>,
+}
+
+/// Part of a frame measured by [`GpuProfiler`]
+#[derive(Clone, Copy)]
+pub(crate) enum GpuScope {
+    #[cfg(feature = "compute")]
+    Compute = 0,
+    Shader = 1,
+    Ui = 2,
+}
+
+const SCOPES: usize = 3;
+const SCOPE_NAMES: [&str; SCOPES] = ["compute", "shader", "ui"];
+
+/// Raw timestamps of one measured frame
+pub(crate) struct GpuFrame {
+    /// When recording of the frame's first scope started on the CPU
+    pub cpu_start: Instant,
+    /// Name, begin and end of each recorded scope in nanoseconds on the GPU's clock
+    pub spans: Vec<(&'static str, f64, f64)>,
+}
+
+/// Weight of the newest measurement in the moving average
+const SMOOTHING: f32 = 0.1;
+
+/// Measures the GPU time of the compute, shader and UI work of a frame with timestamp
+/// queries.
+///
+/// Results are read back without blocking and arrive a few frames later. Frames recorded
+/// while a readback is in flight are not measured.
+pub(crate) struct GpuProfiler {
+    /// Pending measurements hold their scopes and CPU start
+    queries: TimestampQueries<([bool; SCOPES], Instant)>,
+    recording: Recording,
+    timings: [Option<f32>; SCOPES],
+}
+
+impl GpuProfiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        Some(Self {
+            queries: TimestampQueries::new(device, queue, "gpu profiler", 2 * SCOPES as u32)?,
+            recording: Recording::default(),
+            timings: [None; SCOPES],
+        })
+    }
+
+    pub fn timings(&self) -> GpuTimings {
+        let [compute, shader, ui] = self.timings;
+        GpuTimings {
+            compute,
+            shader,
+            ui,
+        }
+    }
+
+    /// Fold in a finished measurement, if any, and return its timestamps
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<GpuFrame> {
+        let result = self.queries.poll(device);
+        self.recording.reading_back = !self.queries.is_idle();
+        let ((recorded, cpu_start), timestamps) = result?;
+        let mut spans = Vec::with_capacity(SCOPES);
+        for (scope, timing) in self.timings.iter_mut().enumerate() {
+            if !recorded[scope] {
+                continue;
+            }
+            let (begin, end) = (timestamps[2 * scope], timestamps[2 * scope + 1]);
+            let time = ((end - begin).max(0.0) * 1e-9) as f32;
+            *timing = Some(match *timing {
+                Some(average) => average + SMOOTHING * (time - average),
+                None => time,
+            });
+            spans.push((SCOPE_NAMES[scope], begin, end));
+        }
+        Some(GpuFrame { cpu_start, spans })
+    }
+
+    /// Record the start timestamp of `scope`
+    pub fn begin(&mut self, encoder: &mut wgpu::CommandEncoder, scope: GpuScope) {
+        if self.recording.begin() {
+            self.queries.write(encoder, 2 * scope as u32);
+        }
+    }
+
+    /// Record the end timestamp of `scope` and resolve both
+    pub fn end(&mut self, encoder: &mut wgpu::CommandEncoder, scope: GpuScope) {
+        if self.recording.end(scope) {
+            let first = 2 * scope as u32;
+            self.queries.write(encoder, first + 1);
+            self.queries.resolve(encoder, first..first + 2);
+        }
+    }
+
+    /// Copy the frame's timestamps for readback. Must be recorded after all scopes of the
+    /// frame, including the compute scope of `update`.
+    pub fn finish_frame(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.recording.can_finish() {
+            self.queries.copy_for_readback(encoder);
+        }
+    }
+
+    /// Start reading back the frame once `finish_frame` was submitted
+    pub fn read_back(&mut self) {
+        if let Some(pending) = self.recording.read_back() {
+            self.queries.read_back(pending);
+        }
+    }
+}
+
+/// Scopes recorded for the next readback. Nothing is recorded while a readback is in
+/// flight, so scopes after `read_back` are not measured in that frame.
+#[derive(Default)]
+struct Recording {
+    reading_back: bool,
+    scopes: [bool; SCOPES],
+    /// When recording of the first of `scopes` started
+    cpu_start: Option<Instant>,
+}
+
+impl Recording {
+    /// Whether the start timestamp should be written
+    fn begin(&mut self) -> bool {
+        if !self.reading_back {
+            self.cpu_start.get_or_insert_with(Instant::now);
+        }
+        !self.reading_back
+    }
+
+    /// Whether the end timestamp should be written and resolved
+    fn end(&mut self, scope: GpuScope) -> bool {
+        if !self.reading_back {
+            self.scopes[scope as usize] = true;
+        }
+        !self.reading_back
+    }
+
+    fn can_finish(&self) -> bool {
+        !self.reading_back && self.scopes.contains(&true)
+    }
+
+    /// Scopes and CPU start of the measurement to read back, if anything was recorded
+    fn read_back(&mut self) -> Option<([bool; SCOPES], Instant)> {
+        if !self.can_finish() {
+            return None;
+        }
+        self.reading_back = true;
+        let cpu_start = self.cpu_start.take().unwrap_or_else(Instant::now);
+        Some((std::mem::take(&mut self.scopes), cpu_start))
+    }
+}
+
+/// Write a timestamp between passes with an empty compute pass, which unlike
+/// `CommandEncoder::write_timestamp` needs no extra feature
+fn write_timestamp(encoder: &mut wgpu::CommandEncoder, query_set: &wgpu::QuerySet, index: u32) {
+    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("timestamp"),
+        timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: None,
+        }),
+    });
+}
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+/// Timestamp queries that are resolved and read back without blocking.
+///
+/// One measurement is read back at a time, `P` is what the caller keeps with it.
+pub(crate) struct TimestampQueries<P> {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Set by the map callback once `readback_buffer` can be read
+    map_state: Arc<AtomicU8>,
+    /// The measurement being read back
+    pending: Option<P>,
+}
+
+impl<P> TimestampQueries<P> {
+    /// `None` if the device lacks `TIMESTAMP_QUERY`
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+        count: u32,
+    ) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some(&format!("{label} queries")),
+            ty: wgpu::QueryType::Timestamp,
+            count,
+        });
+        let size = count as wgpu::BufferAddress * wgpu::QUERY_SIZE as wgpu::BufferAddress;
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{label} resolve buffer")),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{label} readback buffer")),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            map_state: Arc::new(AtomicU8::new(MAP_PENDING)),
+            pending: None,
+        })
+    }
+
+    /// Whether a new measurement can be recorded
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    pub fn write(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        write_timestamp(encoder, &self.query_set, index);
+    }
+
+    /// Resolve the `queries` into the matching range of the resolve buffer
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder, queries: Range<u32>) {
+        let offset = queries.start as wgpu::BufferAddress * wgpu::QUERY_SIZE as wgpu::BufferAddress;
+        encoder.resolve_query_set(&self.query_set, queries, &self.resolve_buffer, offset);
+    }
+
+    /// Copy all resolved timestamps for readback
+    pub fn copy_for_readback(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            self.resolve_buffer.size(),
+        );
+    }
+
+    /// Start reading back once `copy_for_readback` was submitted
+    pub fn read_back(&mut self, pending: P) {
+        self.pending = Some(pending);
+        let map_state = self.map_state.clone();
+        self.readback_buffer
+            .map_async(wgpu::MapMode::Read, .., move |result| {
+                let state = if result.is_ok() { MAP_DONE } else { MAP_FAILED };
+                map_state.store(state, Ordering::Release);
+            });
+    }
+
+    /// The finished measurement, if any, with every timestamp in nanoseconds.
+    /// A failed readback is dropped.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<(P, Vec<f64>)> {
+        self.pending.as_ref()?;
+        let _ = device.poll(wgpu::PollType::Poll);
+        match self.map_state.swap(MAP_PENDING, Ordering::Acquire) {
+            MAP_DONE => {}
+            MAP_FAILED => {
+                self.pending = None;
+                return None;
+            }
+            _ => return None,
+        }
+        let timestamps = self
+            .readback_buffer
+            .slice(..)
+            .get_mapped_range()
+            .chunks_exact(wgpu::QUERY_SIZE as usize)
+            .map(|ticks| {
+                let ticks: u64 = bytemuck::pod_read_unaligned(ticks);
+                ticks as f64 * self.period as f64
+            })
+            .collect();
+        self.readback_buffer.unmap();
+        Some((self.pending.take()?, timestamps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scopes in the order of a windowed frame: `render` records the shader and UI passes,
+    /// then `update` records compute, then the frame ends
+    const FRAME: &[GpuScope] = &[
+        GpuScope::Shader,
+        GpuScope::Ui,
+        #[cfg(feature = "compute")]
+        GpuScope::Compute,
+    ];
+
+    #[test]
+    fn scopes_before_read_back_are_measured() {
+        let mut recording = Recording::default();
+        for &scope in FRAME {
+            assert!(recording.begin());
+            assert!(recording.end(scope));
+        }
+        assert!(recording.can_finish());
+        let (scopes, _) = recording.read_back().unwrap();
+        assert_eq!(scopes, [cfg!(feature = "compute"), true, true]);
+    }
+
+    #[test]
+    fn scopes_after_read_back_are_not_measured() {
+        let mut recording = Recording::default();
+        assert!(recording.begin());
+        assert!(recording.end(GpuScope::Shader));
+        assert!(recording.read_back().is_some());
+        assert!(!recording.begin());
+        assert!(!recording.end(GpuScope::Ui));
+        assert!(!recording.can_finish());
+        assert!(recording.read_back().is_none());
+        // Once the readback finished the next frame is recorded again
+        recording.reading_back = false;
+        assert!(recording.begin());
+        assert!(recording.end(GpuScope::Ui));
+        let (scopes, _) = recording.read_back().unwrap();
+        assert_eq!(scopes, [false, false, true]);
+    }
+
+    #[test]
+    fn empty_frame_is_not_read_back() {
+        let mut recording = Recording::default();
+        assert!(!recording.can_finish());
+        assert!(recording.read_back().is_none());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    Error, Parameters, capture, clock::Clock, context::GraphicsContext,
+    controller::ControllerTrait, render_pass::RenderPass, trace::Trace,
+};
+use egui_winit::winit::dpi::PhysicalSize;
+use glam::UVec2;
+use web_time::Duration;
+
+/// A rendered frame of tightly packed RGBA8 pixels, row by row from the top left
+pub struct Frame {
+    pub size: UVec2,
+    pub data: Vec<u8>,
+}
+
+/// Renders a controller into an offscreen texture without a window or surface.
+///
+/// Works with software adapters such as lavapipe or llvmpipe, so it can be used in CI.
+/// The egui overlay is not drawn.
+pub struct HeadlessRunner<C: ControllerTrait> {
+    rpass: RenderPass,
+    ctx: GraphicsContext,
+    controller: C,
+    target: wgpu::Texture,
+}
+
+impl<C: ControllerTrait + Send> HeadlessRunner<C> {
+    pub fn new(params: Parameters<C>, shader_bytes: &[u8], size: UVec2) -> Result<Self, Error> {
+        let mut controller = params.controller;
+        let mut ctx = futures::executor::block_on(GraphicsContext::new(
+            None,
+            PhysicalSize::new(size.x, size.y),
+            &controller,
+        ))?;
+        let max_dimension = ctx.device.limits().max_texture_dimension_2d;
+        if size.min_element() == 0 || size.max_element() > max_dimension {
+            return Err(Error::InvalidHeadlessSize(size, max_dimension));
+        }
+        ctx.trace = Trace::new(params.options.trace.clone());
+
+        let rpass = RenderPass::new(&ctx, shader_bytes, &mut controller, &params.options)?;
+
+        let target = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Target"),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ctx.config.format,
+            usage: ctx.config.usage,
+            view_formats: &[],
+        });
+
+        Ok(Self {
+            rpass,
+            ctx,
+            controller,
+            target,
+        })
+    }
+
+    /// Drive the clock by exactly `delta` per frame instead of wall-clock time
+    pub fn fixed_timestep(mut self, delta: Duration) -> Self {
+        self.ctx.clock = Clock::fixed(delta);
+        self
+    }
+
+    pub fn controller(&self) -> &C {
+        &self.controller
+    }
+
+    pub fn controller_mut(&mut self) -> &mut C {
+        &mut self.controller
+    }
+
+    pub fn graphics_context(&self) -> &GraphicsContext {
+        &self.ctx
+    }
+
+    /// Render one frame, run the compute update and read the result back.
+    ///
+    /// Blocks until the GPU has finished.
+    pub fn render_frame(&mut self) -> Result<Frame, Error> {
+        self.rpass.update_gpu_profiler(&self.ctx, false);
+        let size = UVec2::new(self.target.width(), self.target.height());
+        let view = self
+            .target
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let viewport =
+            egui::Rect::from_min_size(egui::Pos2::ZERO, size.as_vec2().to_array().into());
+        self.rpass.render_shader(
+            &self.ctx,
+            &self.target,
+            &view,
+            &mut self.controller,
+            viewport,
+        );
+
+        // There is no frame deadline to meet
+        #[cfg(feature = "compute")]
+        self.rpass
+            .update(&self.ctx, &mut self.controller, f32::INFINITY);
+        self.rpass.finish_gpu_profiling(&self.ctx);
+        self.ctx.clock.advance();
+
+        let data = capture::read_texture(&self.ctx, &self.target, UVec2::ZERO, size)?;
+        if let Some(trace) = &self.ctx.trace {
+            trace.end_frame();
+        }
+        Ok(Frame { size, data })
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{Error, GraphicsContext};
+#[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
+use std::{path::PathBuf, time::SystemTime};
+
+/// How the color channels of an [`ImageTexture`] are interpreted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageColorSpace {
+    /// Colors, decoded to linear when sampled
+    #[default]
+    Srgb,
+    /// Data such as normal maps or masks, sampled as stored
+    Linear,
+}
+
+impl ImageColorSpace {
+    fn format(self) -> wgpu::TextureFormat {
+        match self {
+            Self::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            Self::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+/// A PNG or JPEG image uploaded as a texture with a full mip chain.
+///
+/// Bound as two entries: the texture at `binding` and its sampler at `binding + 1`.
+pub struct ImageTexture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    /// File to watch and its modification time when last loaded
+    #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
+    source: Option<(PathBuf, SystemTime)>,
+}
+
+impl ImageTexture {
+    pub fn from_bytes(
+        ctx: &GraphicsContext,
+        bytes: &[u8],
+        color_space: ImageColorSpace,
+        sampler: &wgpu::SamplerDescriptor,
+    ) -> Result<Self, Error> {
+        let image = image::load_from_memory(bytes)?.into_rgba8();
+        let texture = upload(ctx, &image, color_space.format())?;
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Ok(Self {
+            texture,
+            view,
+            sampler: ctx.device.create_sampler(sampler),
+            #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
+            source: None,
+        })
+    }
+
+    /// In hot-reload builds the file is watched, see [`Self::reload_if_changed`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_path(
+        ctx: &GraphicsContext,
+        path: impl AsRef<std::path::Path>,
+        color_space: ImageColorSpace,
+        sampler: &wgpu::SamplerDescriptor,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        #[cfg(feature = "hot-reload-shader")]
+        let modified = std::fs::metadata(path)?.modified()?;
+        #[cfg_attr(not(feature = "hot-reload-shader"), allow(unused_mut))]
+        let mut texture = Self::from_bytes(ctx, &std::fs::read(path)?, color_space, sampler)?;
+        #[cfg(feature = "hot-reload-shader")]
+        {
+            texture.source = Some((path.to_owned(), modified));
+        }
+        Ok(texture)
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// Layout entries of a filterable 2D texture at `binding` and a filtering sampler at
+    /// `binding + 1`
+    pub fn layout_entries(
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+    ) -> [wgpu::BindGroupLayoutEntry; 2] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: binding + 1,
+                visibility,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ]
+    }
+
+    /// Bind group entries matching [`Self::layout_entries`]
+    pub fn bind_group_entries(&self, binding: u32) -> [wgpu::BindGroupEntry<'_>; 2] {
+        [
+            wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(&self.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ]
+    }
+
+    /// Reload the image if its file changed on disk. Cheap enough to call every frame.
+    ///
+    /// Returns `true` if the texture had to be recreated because its size changed, in which
+    /// case the bind groups using it are outdated (see
+    /// [`crate::ControllerTrait::bind_groups_outdated`]).
+    #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
+    pub fn reload_if_changed(&mut self, ctx: &GraphicsContext) -> bool {
+        let Some((path, loaded)) = &mut self.source else {
+            return false;
+        };
+        let Ok(modified) = std::fs::metadata(&*path).and_then(|metadata| metadata.modified())
+        else {
+            return false;
+        };
+        if modified == *loaded {
+            return false;
+        }
+        *loaded = modified;
+        let image = match std::fs::read(&*path)
+            .map_err(Error::from)
+            .and_then(|bytes| Ok(image::load_from_memory(&bytes)?.into_rgba8()))
+        {
+            Ok(image) => image,
+            // Possibly still being written, keep the old image
+            Err(err) => {
+                log::warn!("Failed to reload {}: {err}", path.display());
+                return false;
+            }
+        };
+        if image.dimensions() == (self.texture.width(), self.texture.height()) {
+            write_mips(ctx, &self.texture, image);
+            log::info!("Reloaded {}", path.display());
+            return false;
+        }
+        match upload(ctx, &image, self.texture.format()) {
+            Ok(texture) => {
+                self.view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                self.texture = texture;
+                log::info!("Reloaded {}", path.display());
+                true
+            }
+            Err(err) => {
+                log::warn!("Failed to reload {}: {err}", path.display());
+                false
+            }
+        }
+    }
+}
+
+fn upload(
+    ctx: &GraphicsContext,
+    image: &image::RgbaImage,
+    format: wgpu::TextureFormat,
+) -> Result<wgpu::Texture, Error> {
+    let (width, height) = image.dimensions();
+    let limit = ctx.device.limits().max_texture_dimension_2d;
+    if width > limit || height > limit {
+        return Err(Error::ImageTooLarge(glam::uvec2(width, height), limit));
+    }
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("image texture"),
+        size,
+        mip_level_count: size.max_mips(wgpu::TextureDimension::D2),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    write_mips(ctx, &texture, image.clone());
+    Ok(texture)
+}
+
+/// Write `image` to mip level 0 and downscaled copies of it to the other levels
+fn write_mips(ctx: &GraphicsContext, texture: &wgpu::Texture, mut image: image::RgbaImage) {
+    for mip_level in 0..texture.mip_level_count() {
+        if mip_level > 0 {
+            let (width, height) = image.dimensions();
+            image = image::imageops::resize(
+                &image,
+                (width / 2).max(1),
+                (height / 2).max(1),
+                image::imageops::FilterType::Triangle,
+            );
+        }
+        let (width, height) = image.dimensions();
+        ctx.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &image,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
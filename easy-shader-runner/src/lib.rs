@@ -3,26 +3,68 @@ use std::borrow::Cow;
 use std::path::PathBuf;
 use user_event::CustomEvent;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use capture::save_png;
+pub use clock::Clock;
+#[cfg(feature = "compute")]
+pub use compute_budget::ComputeBudget;
+#[cfg(feature = "compute")]
+pub use compute_kernel::{ComputeKernel, Dispatch};
 pub use context::GraphicsContext;
 pub use controller::ControllerTrait;
+pub use easy_shader_runner_shared::{STANDARD_UNIFORMS_SET, StandardUniforms};
+pub use gpu_buffer::GpuBuffer;
+pub use gpu_profiler::GpuTimings;
+#[cfg(not(target_arch = "wasm32"))]
+pub use headless::{Frame, HeadlessRunner};
+#[cfg(feature = "image")]
+pub use image_texture::{ImageColorSpace, ImageTexture};
+#[cfg(not(target_arch = "wasm32"))]
+pub use offline::{FrameSequence, FrameSequenceOutput};
+pub use shader_pass::ShaderPass;
 pub use ui::UiState;
+pub use vertex_buffer::{IndexBuffer, VertexBuffer};
 
 pub use egui_wgpu::wgpu;
 pub use egui_winit::egui;
 pub use egui_winit::winit;
 
 mod app;
+#[cfg(not(target_arch = "wasm32"))]
+mod capture;
+mod clock;
+mod color_target;
+#[cfg(feature = "compute")]
+mod compute_budget;
+#[cfg(feature = "compute")]
+mod compute_kernel;
 mod context;
 mod controller;
+mod depth;
 mod fps_counter;
+mod gpu_buffer;
+mod gpu_profiler;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+#[cfg(feature = "image")]
+mod image_texture;
+#[cfg(not(target_arch = "wasm32"))]
+mod offline;
+#[cfg(not(target_arch = "wasm32"))]
+mod pipeline_cache;
 mod render_pass;
 #[cfg(all(
     any(feature = "runtime-compilation", feature = "hot-reload-shader"),
     not(target_arch = "wasm32")
 ))]
 mod shader;
+mod shader_pass;
+mod spirv;
+mod standard_uniforms;
+mod trace;
 mod ui;
 mod user_event;
+mod vertex_buffer;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -42,6 +84,92 @@ pub enum Error {
     BuildFailed(spirv_builder::SpirvBuilderError),
     #[error("Build failed")]
     BuildFailedQuietly,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error(transparent)]
+    PngEncodingFailed(#[from] png::EncodingError),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Cannot capture texture format {0:?}")]
+    UnsupportedCaptureFormat(wgpu::TextureFormat),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Failed to map the capture readback buffer: {0}")]
+    CaptureMapFailed(#[from] wgpu::BufferAsyncError),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Failed to wait for the capture readback: {0}")]
+    CapturePollFailed(#[from] wgpu::PollError),
+    #[error("No suitable graphics adapter found: {0}")]
+    NoAdapter(wgpu::RequestAdapterError),
+    #[error(transparent)]
+    RequestDeviceFailed(#[from] wgpu::RequestDeviceError),
+    #[error("Headless size {0} must be non-zero and at most {1} in each dimension")]
+    InvalidHeadlessSize(glam::UVec2, u32),
+    #[cfg(feature = "image")]
+    #[error(transparent)]
+    ImageDecodingFailed(#[from] image::ImageError),
+    #[cfg(feature = "image")]
+    #[error("Image size {0} exceeds the device limit of {1} in each dimension")]
+    ImageTooLarge(glam::UVec2, u32),
+    #[error("Frame rate must be non-zero")]
+    ZeroFrameRate,
+    #[error("{name} push constants are {size} bytes but the device allows at most {limit}")]
+    PushConstantsTooLarge { name: String, size: u32, limit: u32 },
+    #[error("{0}x multisampling is not supported for {1:?}")]
+    UnsupportedSampleCount(u32, wgpu::TextureFormat),
+    #[error("Shader pass format {0:?} cannot be sampled")]
+    UnsampleablePassFormat(wgpu::TextureFormat),
+    #[error("The shader has no fragment entry point {0}")]
+    MissingFragmentEntryPoint(String),
+    #[cfg(feature = "compute")]
+    #[error("No compute kernel {0} is declared by `describe_compute_kernels`")]
+    UnknownComputeKernel(String),
+    #[cfg(feature = "compute")]
+    #[error("{size} bytes of push constants passed to {entry_point}, which declares {declared}")]
+    KernelPushConstantsTooLarge {
+        entry_point: String,
+        size: u32,
+        declared: u32,
+    },
+    #[cfg(feature = "compute")]
+    #[error(
+        "The threads of {0} could not be read from the shader, dispatch it with `Dispatch::Threads`"
+    )]
+    UnknownWorkgroupSize(String),
+    #[cfg(feature = "compute")]
+    #[error(
+        "{entry_point} dispatched with {threads} threads per workgroup, which must be non-zero"
+    )]
+    ZeroThreads {
+        entry_point: String,
+        threads: glam::UVec3,
+    },
+    #[cfg(feature = "compute")]
+    #[error(
+        "{count} workgroups dispatched of {entry_point}, the device allows at most {limit} per dimension"
+    )]
+    TooManyWorkgroups {
+        entry_point: String,
+        count: glam::UVec3,
+        limit: u32,
+    },
+}
+
+/// Names of the shader entry points
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryPoints {
+    pub vertex: String,
+    /// Fragment entry point of the final pass, can be changed at runtime with
+    /// [`UiState::fragment_entry_point`]
+    pub fragment: String,
+    pub compute: String,
+}
+
+impl Default for EntryPoints {
+    fn default() -> Self {
+        Self {
+            vertex: "main_vs".into(),
+            fragment: "main_fs".into(),
+            compute: "main_cs".into(),
+        }
+    }
 }
 
 /// Common parameters and options for all shader runs.
@@ -71,6 +199,61 @@ impl<C: ControllerTrait + Send> Parameters<C> {
         self.options.escape_exits = enable;
         self
     }
+
+    /// Defaults to `main_vs`, `main_fs` and `main_cs`
+    pub fn entry_points(mut self, entry_points: EntryPoints) -> Self {
+        self.options.entry_points = entry_points;
+        self
+    }
+
+    /// Number of samples per pixel of all render passes, 1 disables multisampling.
+    /// Checked against the adapter when rendering starts.
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.options.sample_count = count;
+        self
+    }
+
+    /// Bind [`StandardUniforms`] maintained by the runner to every shader at
+    /// [`STANDARD_UNIFORMS_SET`], shifting all other bind groups up by one. Defaults to off.
+    pub fn standard_uniforms(mut self, enable: bool) -> Self {
+        self.options.standard_uniforms = enable;
+        self
+    }
+
+    /// Key that saves a screenshot of the shader viewport, `None` to disable. Defaults to F12.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn screenshot_key(mut self, key: Option<winit::keyboard::NamedKey>) -> Self {
+        self.options.screenshot_key = key;
+        self
+    }
+
+    /// Directory screenshots are saved to. Defaults to the working directory.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn screenshot_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.screenshot_dir = dir.into();
+        self
+    }
+
+    /// Store compiled pipelines in `dir` and reuse them on later runs and shader reloads.
+    /// Only has an effect on backends supporting pipeline caches (currently Vulkan).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pipeline_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.pipeline_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Record CPU and GPU spans of the first `frames` frames and write them to `path` as a
+    /// Chrome trace JSON file, which Perfetto can load.
+    ///
+    /// Can also be enabled with the `EASY_SHADER_RUNNER_TRACE` environment variable set to the
+    /// path, and `EASY_SHADER_RUNNER_TRACE_FRAMES` to the number of frames (300 by default).
+    /// Headless and offline runs are traced too. A run that ends early writes the frames
+    /// recorded so far.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn trace(mut self, path: impl Into<PathBuf>, frames: u32) -> Self {
+        self.options.trace = Some((path.into(), frames));
+        self
+    }
 }
 
 /// Run with runtime compilation
@@ -113,13 +296,46 @@ pub fn run_with_prebuilt_shader<C: ControllerTrait + Send>(
     start(event_loop, shader_bytes, params)
 }
 
+/// Render `frame_count` frames at `size` without opening a window
+///
+/// Each frame is returned as tightly packed RGBA8 pixels.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_headless_with_prebuilt_shader<C: ControllerTrait + Send>(
+    params: Parameters<C>,
+    shader_bytes: &[u8],
+    size: glam::UVec2,
+    frame_count: u32,
+) -> Result<Vec<Frame>, Error> {
+    setup_logging();
+    let mut runner = HeadlessRunner::new(params, shader_bytes, size)?;
+    (0..frame_count).map(|_| runner.render_frame()).collect()
+}
+
+/// Render a numbered PNG sequence or Y4M file without opening a window
+///
+/// The controller's clock advances by a fixed timestep, so the output does not depend on
+/// how long each frame takes to render.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_frame_sequence<C: ControllerTrait + Send>(
+    params: Parameters<C>,
+    shader_bytes: &[u8],
+    sequence: FrameSequence,
+) -> Result<(), Error> {
+    setup_logging();
+    offline::render(params, shader_bytes, sequence)
+}
+
 fn start<C: ControllerTrait + Send>(
     event_loop: EventLoop<CustomEvent<C>>,
     shader_bytes: impl Into<Cow<'static, [u8]>>,
     params: Parameters<C>,
 ) -> Result<(), Error> {
     let mut app = app::App::new(event_loop.create_proxy(), shader_bytes.into(), params);
-    Ok(event_loop.run_app(&mut app)?)
+    event_loop.run_app(&mut app)?;
+    match app {
+        app::App::Failed(err) => Err(err),
+        _ => Ok(()),
+    }
 }
 
 pub fn setup_logging() {
@@ -0,0 +1,100 @@
+use crate::{Error, Parameters, capture, controller::ControllerTrait, headless::HeadlessRunner};
+use glam::UVec2;
+use std::io::Write;
+use std::path::PathBuf;
+use web_time::Duration;
+
+pub enum FrameSequenceOutput {
+    /// Numbered PNG files `frame-000000.png`, `frame-000001.png`, ... in a directory
+    PngSequence(PathBuf),
+    /// A single uncompressed YUV4MPEG2 (4:4:4) file
+    Y4m(PathBuf),
+}
+
+/// Describes an offline render at a fixed timestep
+pub struct FrameSequence {
+    pub size: UVec2,
+    pub frame_count: u32,
+    /// The clock advances by exactly `1 / frames_per_second` every frame
+    pub frames_per_second: u32,
+    pub output: FrameSequenceOutput,
+}
+
+pub(crate) fn render<C: ControllerTrait + Send>(
+    params: Parameters<C>,
+    shader_bytes: &[u8],
+    sequence: FrameSequence,
+) -> Result<(), Error> {
+    if sequence.frames_per_second == 0 {
+        return Err(Error::ZeroFrameRate);
+    }
+    let delta = Duration::from_secs(1) / sequence.frames_per_second;
+    let mut runner =
+        HeadlessRunner::new(params, shader_bytes, sequence.size)?.fixed_timestep(delta);
+    match sequence.output {
+        FrameSequenceOutput::PngSequence(dir) => {
+            std::fs::create_dir_all(&dir)?;
+            for i in 0..sequence.frame_count {
+                let frame = runner.render_frame()?;
+                capture::save_png(
+                    dir.join(format!("frame-{i:06}.png")),
+                    frame.size,
+                    &frame.data,
+                )?;
+            }
+        }
+        FrameSequenceOutput::Y4m(path) => {
+            let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+            writeln!(
+                writer,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                sequence.size.x, sequence.size.y, sequence.frames_per_second
+            )?;
+            for _ in 0..sequence.frame_count {
+                let frame = runner.render_frame()?;
+                writer.write_all(b"FRAME\n")?;
+                writer.write_all(&rgba_to_yuv444(&frame.data))?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Convert RGBA8 pixels to planar BT.601 limited range YCbCr
+fn rgba_to_yuv444(rgba: &[u8]) -> Vec<u8> {
+    let pixels = rgba.len() / 4;
+    let mut planes = vec![0; pixels * 3];
+    let (y_plane, chroma) = planes.split_at_mut(pixels);
+    let (u_plane, v_plane) = chroma.split_at_mut(pixels);
+    for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+        let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.0);
+        let y = 16.0 + 65.481 * r + 128.553 * g + 24.966 * b;
+        let u = 128.0 - 37.797 * r - 74.203 * g + 112.0 * b;
+        let v = 128.0 + 112.0 * r - 93.786 * g - 18.214 * b;
+        y_plane[i] = y.round() as u8;
+        u_plane[i] = u.round() as u8;
+        v_plane[i] = v.round() as u8;
+    }
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn black_and_white_map_to_limited_range() {
+        assert_eq!(rgba_to_yuv444(&[0, 0, 0, 255]), [16, 128, 128]);
+        assert_eq!(rgba_to_yuv444(&[255, 255, 255, 255]), [235, 128, 128]);
+    }
+
+    #[test]
+    fn planes_are_stored_one_after_another() {
+        let rgba = [0, 0, 0, 255, 255, 255, 255, 255, 255, 0, 0, 0];
+        assert_eq!(
+            rgba_to_yuv444(&rgba),
+            [16, 235, 81, 128, 128, 90, 128, 128, 240]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::GraphicsContext;
+use std::path::{Path, PathBuf};
+
+/// A [`wgpu::PipelineCache`] stored in one file per adapter.
+///
+/// The file starts with a hash of the shader it was built from, so a changed shader starts
+/// over with an empty cache and then replaces the file.
+///
+/// Saved at startup, after a shader reload and when dropped, not on every pipeline rebuild.
+///
+/// Only supported by some backends (currently Vulkan), elsewhere pipelines are compiled
+/// without a cache as before.
+pub(crate) struct PipelineCache {
+    cache: wgpu::PipelineCache,
+    dir: PathBuf,
+    path: PathBuf,
+    shader_hash: u64,
+}
+
+impl PipelineCache {
+    pub fn new(ctx: &GraphicsContext, dir: &Path, shader_bytes: &[u8]) -> Option<Self> {
+        if !ctx
+            .device
+            .features()
+            .contains(wgpu::Features::PIPELINE_CACHE)
+        {
+            return None;
+        }
+        let adapter_key = wgpu::util::pipeline_cache_key(&ctx.adapter.get_info())?;
+        let path = dir.join(adapter_key);
+        let shader_hash = fnv1a(shader_bytes);
+        let data = read(&path, shader_hash);
+        // SAFETY: The file is only ever written by `save` with data from `get_data`, for the
+        // same adapter key. wgpu validates the header and falls back to an empty cache.
+        let cache = unsafe {
+            ctx.device
+                .create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                    label: Some("pipeline cache"),
+                    data: data.as_deref(),
+                    fallback: true,
+                })
+        };
+        Some(Self {
+            cache,
+            dir: dir.to_owned(),
+            path,
+            shader_hash,
+        })
+    }
+
+    pub fn cache(&self) -> &wgpu::PipelineCache {
+        &self.cache
+    }
+
+    #[cfg(feature = "hot-reload-shader")]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write the cache to disk, replacing the file atomically
+    pub fn save(&self) {
+        let Some(data) = self.cache.get_data() else {
+            return;
+        };
+        if let Err(err) = write(&self.dir, &self.path, self.shader_hash, &data) {
+            log::warn!(
+                "Failed to save pipeline cache to {}: {err}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+impl Drop for PipelineCache {
+    /// Keep the pipelines compiled since the last save
+    fn drop(&mut self) {
+        self.save();
+    }
+}
+
+/// Cache data at `path` if it was saved for the shader with `shader_hash`
+fn read(path: &Path, shader_hash: u64) -> Option<Vec<u8>> {
+    let file = std::fs::read(path).ok()?;
+    let (hash, data) = file.split_first_chunk::<8>()?;
+    (u64::from_le_bytes(*hash) == shader_hash).then(|| data.to_vec())
+}
+
+/// Write `data` for the shader with `shader_hash`, replacing the file at `path` atomically
+fn write(dir: &Path, path: &Path, shader_hash: u64, data: &[u8]) -> std::io::Result<()> {
+    let temp = path.with_extension("tmp");
+    let file = [&shader_hash.to_le_bytes()[..], data].concat();
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&temp, file)?;
+    std::fs::rename(&temp, path)
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is the same across Rust releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn data_is_only_loaded_for_the_same_shader() {
+        let dir = std::env::temp_dir().join(format!("esr-pipeline-cache-{}", std::process::id()));
+        let path = dir.join("wgpu_pipeline_cache_vulkan_1002_73bf");
+        let hash = fnv1a(b"shader");
+        assert_eq!(read(&path, hash), None);
+
+        write(&dir, &path, hash, b"data").unwrap();
+        assert_eq!(read(&path, hash), Some(b"data".to_vec()));
+        assert_eq!(read(&path, fnv1a(b"other shader")), None);
+
+        // Saving for a changed shader replaces the file
+        write(&dir, &path, fnv1a(b"other shader"), b"other data").unwrap();
+        assert_eq!(read(&path, hash), None);
+        assert_eq!(
+            read(&path, fnv1a(b"other shader")),
+            Some(b"other data".to_vec())
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::write(&path, [0; 4]).unwrap();
+        assert_eq!(read(&path, hash), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,40 +1,103 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::pipeline_cache::PipelineCache;
 use crate::{
+    EntryPoints, Error,
+    color_target::ColorTarget,
     context::GraphicsContext,
     controller::ControllerTrait,
-    ui::{Ui, UiState},
+    depth::DepthTarget,
+    gpu_profiler::{GpuProfiler, GpuScope},
+    shader_pass::{PassTargets, ShaderPass},
+    spirv,
+    standard_uniforms::StandardUniformsBuffer,
+    ui::{Options, Ui, UiState},
+};
+#[cfg(feature = "compute")]
+use crate::{
+    compute_budget::{ComputeBudget, ComputeTimer},
+    compute_kernel::{ComputeKernel, Dispatch, Workgroups, reflect_workgroup_size},
 };
 use egui_winit::winit::window::Window;
 
 #[cfg(feature = "emulate_constants")]
 struct EmulateConstantsBuffer {
     render: wgpu::Buffer,
+    /// One slot per dispatch, selected with a dynamic offset
     #[cfg(feature = "compute")]
     compute: wgpu::Buffer,
+    /// Distance between the slots of `compute`
+    #[cfg(feature = "compute")]
+    compute_stride: u32,
 }
 
+/// Dispatches with emulated push constants recorded before their constants are uploaded
+/// and the encoder is submitted
+#[cfg(all(feature = "emulate_constants", feature = "compute"))]
+const EMULATED_DISPATCHES_PER_SUBMIT: u32 = 256;
+
+/// Dynamic offsets of the emulated push constants bind group while rendering
+#[cfg(feature = "emulate_constants")]
+const EMULATE_CONSTANTS_RENDER_OFFSETS: &[u32] = if cfg!(feature = "compute") { &[0] } else { &[] };
+
 struct Pipelines {
     render: wgpu::RenderPipeline,
+    /// One per [`ShaderPass`], in order
+    passes: Vec<wgpu::RenderPipeline>,
+    /// One per [`ComputeKernel`], in order
     #[cfg(feature = "compute")]
-    compute: wgpu::ComputePipeline,
+    compute: Vec<wgpu::ComputePipeline>,
 }
 
 struct PipelineLayouts {
     render: wgpu::PipelineLayout,
     #[cfg(feature = "compute")]
-    compute: wgpu::PipelineLayout,
+    compute: Vec<wgpu::PipelineLayout>,
 }
 
 pub struct RenderPass {
     pipelines: Pipelines,
-    #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
     pipeline_layouts: PipelineLayouts,
+    module: wgpu::ShaderModule,
+    entry_points: EntryPoints,
+    /// What the final pass can switch to, `None` if the shader could not be read
+    fragment_entry_points: Option<Vec<String>>,
     ui_renderer: egui_wgpu::Renderer,
+    /// The controller's, see [`ControllerTrait::describe_bind_groups`]
+    bind_group_layouts: Vec<wgpu::BindGroupLayout>,
     bind_groups: Vec<wgpu::BindGroup>,
+    render_push_constant_size: u32,
+    shader_passes: Vec<ShaderPass>,
+    #[cfg(feature = "compute")]
+    compute_kernels: Vec<ComputeKernel>,
+    /// `threads` declared by each kernel, `None` if they could not be read from the shader
+    #[cfg(feature = "compute")]
+    workgroup_sizes: Vec<Option<glam::UVec3>>,
+    #[cfg(feature = "compute")]
+    compute_timer: Option<ComputeTimer>,
+    /// Only exists while the GPU timings are shown, see [`UiState::show_gpu_timings`], or a
+    /// trace is recorded
+    gpu_profiler: Option<GpuProfiler>,
+    pass_targets: Option<PassTargets>,
+    standard_uniforms: Option<StandardUniformsBuffer>,
+    depth_target: Option<DepthTarget>,
+    /// Multisampled color attachment of the final pass, resolved into the output
+    multisample_target: Option<ColorTarget>,
+    /// Keeps the final pass's contents between frames while it loads instead of clearing,
+    /// only needed without multisampling
+    accumulation_target: Option<ColorTarget>,
+    sample_count: u32,
+    primitive: wgpu::PrimitiveState,
+    blend: Option<wgpu::BlendState>,
     shader_viewport: egui::Rect,
     #[cfg(feature = "emulate_constants")]
     emulate_constants_buffer: EmulateConstantsBuffer,
-    #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
+    #[cfg(feature = "emulate_constants")]
+    emulate_constants_layout: wgpu::BindGroupLayout,
+    #[cfg(feature = "emulate_constants")]
+    emulate_constants_bind_group: wgpu::BindGroup,
     vertex_buffer_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pipeline_cache: Option<PipelineCache>,
 }
 
 impl RenderPass {
@@ -42,89 +105,345 @@ impl RenderPass {
         ctx: &GraphicsContext,
         shader_bytes: &[u8],
         controller: &mut C,
-    ) -> Self {
+        options: &Options,
+    ) -> Result<Self, Error> {
+        let entry_points = options.entry_points.clone();
+        let sample_count = options.sample_count;
+        let surface_size = glam::uvec2(ctx.config.width, ctx.config.height);
         let (layouts, bind_groups) = controller.describe_bind_groups(ctx);
-        let bind_group_layouts = layouts.iter();
+        let shader_passes = controller.describe_shader_passes(ctx);
+        let depth_stencil = controller.describe_depth_stencil(ctx);
+
+        check_sample_count(ctx, ctx.config.format, sample_count, true)?;
+        for pass in &shader_passes {
+            check_sample_count(ctx, pass.format, sample_count, true)?;
+        }
+        if let Some(state) = &depth_stencil {
+            check_sample_count(ctx, state.format, sample_count, false)?;
+        }
+        let multisample_target = (sample_count > 1)
+            .then(|| ColorTarget::new(&ctx.device, ctx.config.format, sample_count, surface_size));
+        let depth_target = depth_stencil
+            .map(|state| DepthTarget::new(&ctx.device, state, sample_count, surface_size));
+        let pass_targets = (!shader_passes.is_empty())
+            .then(|| PassTargets::new(&ctx.device, &shader_passes, sample_count, surface_size))
+            .transpose()?;
+        let standard_uniforms = options
+            .standard_uniforms
+            .then(|| StandardUniformsBuffer::new(&ctx.device));
+        let bind_group_layouts = standard_uniforms
+            .as_ref()
+            .map(|uniforms| &uniforms.layout)
+            .into_iter()
+            .chain(&layouts)
+            .chain(pass_targets.as_ref().map(|targets| &targets.layout));
+
+        #[cfg(feature = "compute")]
+        let compute_kernels = {
+            let kernels = controller.describe_compute_kernels(ctx);
+            if kernels.is_empty() {
+                vec![ComputeKernel::new::<C::ComputeConstants>(
+                    entry_points.compute.clone(),
+                )]
+            } else {
+                kernels
+            }
+        };
+
+        let render_push_constant_size = (std::mem::size_of::<C::PushConstants>() as u32)
+            .next_multiple_of(wgpu::PUSH_CONSTANT_ALIGNMENT);
+        check_push_constant_size(&ctx.device, "Render", render_push_constant_size)?;
+        #[cfg(feature = "compute")]
+        for kernel in &compute_kernels {
+            check_push_constant_size(&ctx.device, &kernel.entry_point, kernel.push_constant_size)?;
+        }
 
         #[cfg(feature = "emulate_constants")]
         let (emulate_constants_layout, emulate_constants_bind_group, emulate_constants_buffer) =
-            create_emulate_constants_bind_groups(&ctx.device);
+            create_emulate_constants_bind_groups(
+                &ctx.device,
+                render_push_constant_size,
+                #[cfg(feature = "compute")]
+                compute_kernels
+                    .iter()
+                    .map(|kernel| kernel.push_constant_size)
+                    .max()
+                    .unwrap_or(0),
+            );
         #[cfg(feature = "emulate_constants")]
         let bind_group_layouts = bind_group_layouts.chain([&emulate_constants_layout]);
-        #[cfg(feature = "emulate_constants")]
-        let bind_groups = bind_groups
-            .into_iter()
-            .chain([emulate_constants_bind_group])
-            .collect::<Vec<_>>();
 
         let vertex_buffer_layouts = controller.describe_vertex_buffer_layouts(ctx);
-        let pipeline_layouts =
-            create_pipeline_layouts(ctx, &bind_group_layouts.collect::<Vec<_>>());
+        let primitive = controller.describe_primitive_state(ctx);
+        let blend = controller.describe_blend_state(ctx);
+        let pipeline_layouts = create_pipeline_layouts(
+            ctx,
+            &bind_group_layouts.collect::<Vec<_>>(),
+            render_push_constant_size,
+            #[cfg(feature = "compute")]
+            &compute_kernels,
+        );
+        let module = create_shader_module(&ctx.device, shader_bytes);
+        #[cfg(not(target_arch = "wasm32"))]
+        let pipeline_cache = options
+            .pipeline_cache_dir
+            .as_deref()
+            .and_then(|dir| PipelineCache::new(ctx, dir, shader_bytes));
+        #[cfg(not(target_arch = "wasm32"))]
+        let cache = pipeline_cache.as_ref().map(PipelineCache::cache);
+        #[cfg(target_arch = "wasm32")]
+        let cache = None;
         let pipelines = create_pipelines(
             &ctx.device,
             &pipeline_layouts,
             ctx.config.format,
             &vertex_buffer_layouts,
-            shader_bytes,
+            depth_target.as_ref().map(|depth| &depth.state),
+            sample_count,
+            primitive,
+            blend,
+            &shader_passes,
+            #[cfg(feature = "compute")]
+            &compute_kernels,
+            &module,
+            &entry_points,
+            cache,
         );
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(pipeline_cache) = &pipeline_cache {
+            pipeline_cache.save();
+        }
 
         let ui_renderer = egui_wgpu::Renderer::new(
             &ctx.device,
             ctx.config.format,
+            // egui antialiases by itself, and drawing straight into the output leaves the
+            // multisampled target to the final pass
             egui_wgpu::RendererOptions {
                 msaa_samples: 1,
-                depth_stencil_format: None,
+                depth_stencil_format: depth_target.as_ref().map(|depth| depth.state.format),
                 dithering: false,
                 predictable_texture_filtering: false,
             },
         );
 
-        Self {
+        Ok(Self {
             pipelines,
-            #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
             pipeline_layouts,
+            module,
+            entry_points,
+            fragment_entry_points: spirv::fragment_entry_points(shader_bytes),
             ui_renderer,
+            bind_group_layouts: layouts,
             bind_groups,
+            render_push_constant_size,
+            shader_passes,
+            #[cfg(feature = "compute")]
+            workgroup_sizes: reflect_workgroup_sizes(shader_bytes, &compute_kernels),
+            #[cfg(feature = "compute")]
+            compute_kernels,
+            #[cfg(feature = "compute")]
+            compute_timer: ComputeTimer::new(&ctx.device, &ctx.queue),
+            gpu_profiler: None,
+            pass_targets,
+            standard_uniforms,
+            depth_target,
+            multisample_target,
+            accumulation_target: None,
+            sample_count,
+            primitive,
+            blend,
             shader_viewport: egui::Rect::NAN,
             #[cfg(feature = "emulate_constants")]
             emulate_constants_buffer,
-            #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
+            #[cfg(feature = "emulate_constants")]
+            emulate_constants_layout,
+            #[cfg(feature = "emulate_constants")]
+            emulate_constants_bind_group,
             vertex_buffer_layouts,
-        }
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline_cache,
+        })
     }
 
+    /// Run the controller's compute update, recording every dispatch into one encoder
+    /// that is submitted once at the end.
+    ///
+    /// When timestamp queries are supported the GPU time of the dispatches is measured and
+    /// passed on in the next [`ComputeBudget`]s.
     #[cfg(feature = "compute")]
-    pub fn compute(
-        &self,
+    pub fn update<C: ControllerTrait>(
+        &mut self,
         ctx: &GraphicsContext,
-        dimensions: glam::UVec3,
-        threads: glam::UVec3,
-        push_constants: &[u8],
+        controller: &mut C,
+        allowed_duration: f32,
     ) {
-        let workspace = (dimensions.as_vec3() / threads.as_vec3()).ceil().as_uvec3();
+        let mut timer = self.compute_timer.take();
+        if let Some(timer) = &mut timer {
+            timer.poll(&ctx.device);
+        }
+        let budget = ComputeBudget {
+            allowed_duration,
+            gpu_time_per_dispatch: timer.as_ref().and_then(ComputeTimer::gpu_time_per_dispatch),
+        };
+        let timer_running = timer.as_ref().filter(|timer| timer.is_idle());
+
         let mut encoder = ctx
             .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        {
-            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: None,
-                timestamp_writes: None,
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Compute Encoder"),
             });
+        if let Some(timer) = timer_running {
+            timer.begin(&mut encoder);
+        }
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.begin(&mut encoder, GpuScope::Compute);
+        }
+        let encoder = std::cell::RefCell::new(encoder);
+        #[cfg(feature = "emulate_constants")]
+        let constants = std::cell::RefCell::new(Vec::new());
+        let dispatches = std::cell::Cell::new(0);
+        let span = ctx.trace_span("update");
+        controller.update(
+            ctx,
+            |entry_point, dispatch, push_constants| {
+                self.compute(
+                    ctx,
+                    &mut encoder.borrow_mut(),
+                    #[cfg(feature = "emulate_constants")]
+                    &mut constants.borrow_mut(),
+                    entry_point,
+                    dispatch,
+                    push_constants,
+                );
+                dispatches.set(dispatches.get() + 1);
+            },
+            budget,
+        );
+        drop(span);
+        let mut encoder = encoder.into_inner();
+        #[cfg(feature = "emulate_constants")]
+        ctx.queue.write_buffer(
+            &self.emulate_constants_buffer.compute,
+            0,
+            &constants.into_inner(),
+        );
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.end(&mut encoder, GpuScope::Compute);
+        }
+        let measure = timer_running.is_some() && dispatches.get() > 0;
+        if measure && let Some(timer) = &timer {
+            timer.end(&mut encoder);
+        }
+        {
+            let _span = ctx.trace_span("submit");
+            ctx.queue.submit(Some(encoder.finish()));
+        }
+        if measure && let Some(timer) = &mut timer {
+            timer.read_back(dispatches.get());
+        }
+        self.compute_timer = timer;
+    }
 
-            cpass.set_pipeline(&self.pipelines.compute);
-            {
-                #[cfg(not(feature = "emulate_constants"))]
-                cpass.set_push_constants(0, push_constants);
-                #[cfg(feature = "emulate_constants")]
-                ctx.queue
-                    .write_buffer(&self.emulate_constants_buffer.compute, 0, push_constants);
+    /// Record a single dispatch.
+    ///
+    /// With emulated push constants, `constants` collects the constants of the dispatches
+    /// recorded into `encoder`, which must be uploaded before it is submitted.
+    #[cfg(feature = "compute")]
+    pub fn compute(
+        &self,
+        ctx: &GraphicsContext,
+        encoder: &mut wgpu::CommandEncoder,
+        #[cfg(feature = "emulate_constants")] constants: &mut Vec<u8>,
+        entry_point: &str,
+        dispatch: Dispatch<'_>,
+        push_constants: &[u8],
+    ) {
+        let Some(kernel) = self
+            .compute_kernels
+            .iter()
+            .position(|kernel| kernel.entry_point == entry_point)
+        else {
+            log::error!(
+                "Skipped dispatch: {}",
+                Error::UnknownComputeKernel(entry_point.to_owned())
+            );
+            return;
+        };
+        let (pipeline, declared) = (
+            &self.pipelines.compute[kernel],
+            &self.compute_kernels[kernel],
+        );
+        if push_constants.len() as u32 > declared.push_constant_size {
+            let err = Error::KernelPushConstantsTooLarge {
+                entry_point: declared.entry_point.clone(),
+                size: push_constants.len() as u32,
+                declared: declared.push_constant_size,
+            };
+            log::error!("Skipped dispatch: {err}");
+            return;
+        }
+        let limit = ctx.device.limits().max_compute_workgroups_per_dimension;
+        let workgroups =
+            match dispatch.workgroups(&declared.entry_point, self.workgroup_sizes[kernel], limit) {
+                Ok(workgroups) => workgroups,
+                Err(err) => {
+                    log::error!("Skipped dispatch: {err}");
+                    return;
+                }
+            };
+        // A queued `write_buffer` lands before the whole encoder runs, so each dispatch
+        // reads its constants from a slot of its own
+        #[cfg(feature = "emulate_constants")]
+        let constants_offset = {
+            let buffer = &self.emulate_constants_buffer;
+            // Out of slots, submit the dispatches so far so theirs can be reused
+            if constants.len() as wgpu::BufferAddress == buffer.compute.size() {
+                ctx.queue.write_buffer(&buffer.compute, 0, constants);
+                constants.clear();
+                let full = std::mem::replace(
+                    encoder,
+                    ctx.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("Compute Encoder"),
+                        }),
+                );
+                ctx.queue.submit(Some(full.finish()));
             }
-            for (i, bind_group) in self.bind_groups.iter().enumerate() {
-                cpass.set_bind_group(i as u32, bind_group, &[]);
+            let offset = constants.len() as u32;
+            constants.extend_from_slice(push_constants);
+            constants.resize((offset + buffer.compute_stride) as usize, 0);
+            offset
+        };
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: None,
+            timestamp_writes: None,
+        });
+
+        cpass.set_pipeline(pipeline);
+        #[cfg(not(feature = "emulate_constants"))]
+        if !push_constants.is_empty() {
+            cpass.set_push_constants(0, push_constants);
+        }
+        let pass_targets = self
+            .pass_targets
+            .as_ref()
+            .map(PassTargets::current_bind_group);
+        for (i, bind_group) in self.all_bind_groups(pass_targets).enumerate() {
+            cpass.set_bind_group(i as u32, bind_group, &[]);
+        }
+        #[cfg(feature = "emulate_constants")]
+        cpass.set_bind_group(
+            self.all_bind_groups(pass_targets).count() as u32,
+            &self.emulate_constants_bind_group,
+            &[constants_offset],
+        );
+        match workgroups {
+            Workgroups::Count(count) => cpass.dispatch_workgroups(count.x, count.y, count.z),
+            Workgroups::Indirect { buffer, offset } => {
+                cpass.dispatch_workgroups_indirect(buffer, offset);
             }
-            cpass.dispatch_workgroups(workspace.x, workspace.y, workspace.z);
         }
-        ctx.queue.submit(Some(encoder.finish()));
     }
 
     pub fn render<C: ControllerTrait>(
@@ -135,13 +454,17 @@ impl RenderPass {
         ui_state: &mut UiState,
         controller: &mut C,
     ) -> Result<(), wgpu::SurfaceError> {
-        let output = match ctx.surface.get_current_texture() {
+        let surface = ctx
+            .surface
+            .as_ref()
+            .expect("rendering to a window requires a surface");
+        let output = match surface.get_current_texture() {
             Ok(surface_texture) => surface_texture,
             Err(err) => {
                 eprintln!("get_current_texture error: {err:?}");
                 return match err {
                     wgpu::SurfaceError::Lost => {
-                        ctx.surface.configure(&ctx.device, &ctx.config);
+                        surface.configure(&ctx.device, &ctx.config);
                         Ok(())
                     }
                     _ => Err(err),
@@ -152,100 +475,332 @@ impl RenderPass {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        self.render_ui(ctx, &output_view, window, ui, ui_state, controller);
+        self.render_ui(
+            ctx,
+            &output.texture,
+            &output_view,
+            window,
+            ui,
+            ui_state,
+            controller,
+        );
 
         output.present();
 
         Ok(())
     }
 
-    fn render_shader<C: ControllerTrait>(
+    pub(crate) fn render_shader<C: ControllerTrait>(
         &mut self,
         ctx: &GraphicsContext,
+        output_texture: &wgpu::Texture,
         output_view: &wgpu::TextureView,
         controller: &mut C,
         available_rect: egui::Rect,
     ) {
+        let size = glam::vec2(available_rect.width(), available_rect.height()).floor();
+        let mut rebuild_bind_groups = controller.bind_groups_outdated();
+        if self.shader_viewport != available_rect {
+            self.shader_viewport = available_rect;
+            controller.resize(size.as_uvec2());
+            rebuild_bind_groups |= controller.rebuild_bind_groups_on_resize();
+        }
+        if rebuild_bind_groups {
+            self.rebuild_bind_groups(ctx, controller);
+        }
+        let offset = self.shader_offset();
+
+        if let Some(standard_uniforms) = &self.standard_uniforms {
+            standard_uniforms.write(&ctx.queue, &ctx.clock, size, offset);
+        }
+        let push_constants = {
+            let _span = ctx.trace_span("prepare_render");
+            controller.prepare_render(ctx, offset)
+        };
+        let push_constants = bytemuck::bytes_of(&push_constants);
+        #[cfg(feature = "emulate_constants")]
+        ctx.queue
+            .write_buffer(&self.emulate_constants_buffer.render, 0, push_constants);
+
+        let surface_size = glam::uvec2(ctx.config.width, ctx.config.height);
+        if let Some(pass_targets) = &mut self.pass_targets {
+            pass_targets.resize(&ctx.device, &self.shader_passes, surface_size);
+            pass_targets.swap();
+        }
+        self.resize_attachments(ctx);
+
         let mut encoder = ctx
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Shader Encoder"),
             });
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.begin(&mut encoder, GpuScope::Shader);
+        }
+        if let Some(pass_targets) = &self.pass_targets {
+            for (i, pipeline) in self.pipelines.passes.iter().enumerate() {
+                let (view, resolve_target) = pass_targets.output_views(i);
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Shader Pass"),
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        depth_slice: None,
+                        resolve_target,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: self
+                        .depth_target
+                        .as_ref()
+                        .map(DepthTarget::attachment),
+                });
+                rpass.set_viewport(offset.x, offset.y, size.x, size.y, 0.0, 1.0);
+                self.draw(
+                    &mut rpass,
+                    pipeline,
+                    controller,
+                    push_constants,
+                    Some(pass_targets.previous_bind_group()),
+                );
+            }
+        }
+        let load = controller.get_load_op();
+        // The multisampled target is only drawn to by this pass, so it keeps its contents.
+        // Without it, the surface texture changes every frame and the UI pass draws over it,
+        // so loading draws into a texture of our own that is then copied to the surface.
+        let accumulate = load == wgpu::LoadOp::Load
+            && self.multisample_target.is_none()
+            && output_texture
+                .usage()
+                .contains(wgpu::TextureUsages::COPY_DST);
+        if accumulate && self.accumulation_target.is_none() {
+            self.accumulation_target = Some(ColorTarget::new(
+                &ctx.device,
+                ctx.config.format,
+                1,
+                surface_size,
+            ));
+        }
+        let accumulation_target = self.accumulation_target.as_ref().filter(|_| accumulate);
         {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Shader Render Pass"),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: output_view,
+            let color_attachment = match accumulation_target {
+                Some(target) => wgpu::RenderPassColorAttachment {
+                    view: target.view(),
                     depth_slice: None,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                        load,
                         store: wgpu::StoreOp::Store,
                     },
-                })],
-                depth_stencil_attachment: None,
+                },
+                None => self.color_attachment(output_view, load),
+            };
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shader Render Pass"),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                color_attachments: &[Some(color_attachment)],
+                depth_stencil_attachment: self.depth_target.as_ref().map(DepthTarget::attachment),
             });
-
-            let size = glam::vec2(available_rect.width(), available_rect.height()).floor();
-            if self.shader_viewport != available_rect {
-                self.shader_viewport = available_rect;
-                controller.resize(size.as_uvec2());
-            }
-            let offset = self.shader_offset();
             rpass.set_viewport(offset.x, offset.y, size.x, size.y, 0.0, 1.0);
+            self.draw(
+                &mut rpass,
+                &self.pipelines.render,
+                controller,
+                push_constants,
+                self.pass_targets
+                    .as_ref()
+                    .map(PassTargets::current_bind_group),
+            );
+        }
+        if let Some(source) = accumulation_target {
+            encoder.copy_texture_to_texture(
+                source.texture().as_image_copy(),
+                output_texture.as_image_copy(),
+                source.texture().size(),
+            );
+        }
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.end(&mut encoder, GpuScope::Shader);
+        }
+
+        let _span = ctx.trace_span("submit");
+        ctx.queue.submit(Some(encoder.finish()));
+    }
 
-            rpass.set_pipeline(&self.pipelines.render);
-            {
-                let push_constants = controller.prepare_render(ctx, offset);
-                let bytes = bytemuck::bytes_of(&push_constants);
-                #[cfg(not(feature = "emulate_constants"))]
-                rpass.set_push_constants(wgpu::ShaderStages::FRAGMENT, 0, bytes);
-                #[cfg(feature = "emulate_constants")]
-                ctx.queue
-                    .write_buffer(&self.emulate_constants_buffer.render, 0, bytes);
+    fn draw<C: ControllerTrait>(
+        &self,
+        rpass: &mut wgpu::RenderPass,
+        pipeline: &wgpu::RenderPipeline,
+        controller: &C,
+        #[cfg_attr(feature = "emulate_constants", allow(unused_variables))] push_constants: &[u8],
+        pass_targets: Option<&wgpu::BindGroup>,
+    ) {
+        rpass.set_pipeline(pipeline);
+        #[cfg(not(feature = "emulate_constants"))]
+        if !push_constants.is_empty() {
+            rpass.set_push_constants(wgpu::ShaderStages::VERTEX_FRAGMENT, 0, push_constants);
+        }
+        for (i, bind_group) in self.all_bind_groups(pass_targets).enumerate() {
+            rpass.set_bind_group(i as u32, bind_group, &[]);
+        }
+        #[cfg(feature = "emulate_constants")]
+        rpass.set_bind_group(
+            self.all_bind_groups(pass_targets).count() as u32,
+            &self.emulate_constants_bind_group,
+            EMULATE_CONSTANTS_RENDER_OFFSETS,
+        );
+        let (vertices, index_buffer) = controller.get_vertex_index_buffer();
+        let (procedural_vertices, num_instances) = controller.get_vertex_instance_count();
+        let num_vertices = match vertices {
+            Some((vertex_buffers, num_vertices)) => {
+                for vertex_buffer in vertex_buffers {
+                    rpass.set_vertex_buffer(vertex_buffer.slot, vertex_buffer.buffer);
+                }
+                num_vertices
             }
-            for (i, bind_group) in self.bind_groups.iter().enumerate() {
-                rpass.set_bind_group(i as u32, bind_group, &[]);
+            None => procedural_vertices,
+        };
+        for instance_buffer in controller.get_instance_buffers() {
+            rpass.set_vertex_buffer(instance_buffer.slot, instance_buffer.buffer);
+        }
+        let indirect = controller.get_indirect_buffer();
+        match (index_buffer, indirect) {
+            (Some(index_buffer), Some((buffer, offset))) => {
+                rpass.set_index_buffer(index_buffer.buffer, index_buffer.format);
+                rpass.draw_indexed_indirect(buffer, offset);
             }
-            let (vertices, indices) = controller.get_vertex_index_buffer();
-            if let Some((vertex_buffer, num_vertices)) = vertices {
-                rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                if let Some((index_buffer, num_indices)) = indices {
-                    rpass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                    rpass.draw_indexed(0..num_indices, 0, 0..1);
-                } else {
-                    rpass.draw(0..num_vertices, 0..1);
-                }
-            } else {
-                rpass.draw(0..3, 0..1);
+            (Some(index_buffer), None) => {
+                rpass.set_index_buffer(index_buffer.buffer, index_buffer.format);
+                rpass.draw_indexed(0..index_buffer.count, 0, 0..num_instances);
             }
+            (None, Some((buffer, offset))) => rpass.draw_indirect(buffer, offset),
+            (None, None) => rpass.draw(0..num_vertices, 0..num_instances),
         }
+    }
 
-        ctx.queue.submit(Some(encoder.finish()));
+    /// Color attachment of the final pass, drawing into the multisampled target and
+    /// resolving into `output_view` when multisampling
+    fn color_attachment<'a>(
+        &'a self,
+        output_view: &'a wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        let (view, resolve_target) = match &self.multisample_target {
+            Some(target) => (target.view(), Some(output_view)),
+            None => (output_view, None),
+        };
+        wgpu::RenderPassColorAttachment {
+            view,
+            depth_slice: None,
+            resolve_target,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        }
     }
 
+    /// Recreate the attachments owned by the runner if the surface size changed
+    fn resize_attachments(&mut self, ctx: &GraphicsContext) {
+        let surface_size = glam::uvec2(ctx.config.width, ctx.config.height);
+        if let Some(depth_target) = &mut self.depth_target {
+            depth_target.resize(&ctx.device, surface_size);
+        }
+        for target in [&mut self.multisample_target, &mut self.accumulation_target]
+            .into_iter()
+            .flatten()
+        {
+            target.resize(&ctx.device, surface_size);
+        }
+    }
+
+    /// Ask the controller for its bind groups again. The pipelines are only recreated if the
+    /// layouts changed.
+    fn rebuild_bind_groups<C: ControllerTrait>(
+        &mut self,
+        ctx: &GraphicsContext,
+        controller: &mut C,
+    ) {
+        let (layouts, bind_groups) = controller.describe_bind_groups(ctx);
+        self.bind_groups = bind_groups;
+        if layouts != self.bind_group_layouts {
+            self.bind_group_layouts = layouts;
+            let bind_group_layouts = self
+                .standard_uniforms
+                .as_ref()
+                .map(|uniforms| &uniforms.layout)
+                .into_iter()
+                .chain(&self.bind_group_layouts)
+                .chain(self.pass_targets.as_ref().map(|targets| &targets.layout));
+            #[cfg(feature = "emulate_constants")]
+            let bind_group_layouts = bind_group_layouts.chain([&self.emulate_constants_layout]);
+            self.pipeline_layouts = create_pipeline_layouts(
+                ctx,
+                &bind_group_layouts.collect::<Vec<_>>(),
+                self.render_push_constant_size,
+                #[cfg(feature = "compute")]
+                &self.compute_kernels,
+            );
+            self.rebuild_pipelines(ctx);
+        }
+    }
+
+    /// Bind groups in set order: the standard uniforms if enabled, the controller's, then the
+    /// shader pass outputs. The emulated push constants follow them.
+    fn all_bind_groups<'a>(
+        &'a self,
+        pass_targets: Option<&'a wgpu::BindGroup>,
+    ) -> impl Iterator<Item = &'a wgpu::BindGroup> {
+        self.standard_uniforms
+            .as_ref()
+            .map(|uniforms| &uniforms.bind_group)
+            .into_iter()
+            .chain(&self.bind_groups)
+            .chain(pass_targets)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn render_ui<C: ControllerTrait>(
         &mut self,
         ctx: &GraphicsContext,
+        output_texture: &wgpu::Texture,
         output_view: &wgpu::TextureView,
         window: &Window,
         ui: &mut Ui,
         ui_state: &mut UiState,
         controller: &mut C,
     ) {
+        self.update_gpu_profiler(ctx, ui_state.show_gpu_timings);
+        ui_state.gpu_timings = self.gpu_profiler.as_ref().map(GpuProfiler::timings);
         let (clipped_primitives, textures_delta, available_rect, pixels_per_point) =
             ui.prepare(window, ui_state, controller, ctx);
+        if let Err(err) = self.set_fragment_entry_point(ctx, &ui_state.fragment_entry_point) {
+            log::error!("{err}");
+            ui_state.fragment_entry_point = self.entry_points.fragment.clone();
+        }
+        // `Some(include_ui)` if a screenshot should be taken this frame
+        #[cfg(not(target_arch = "wasm32"))]
+        let screenshot = std::mem::take(&mut ui_state.screenshot_requested)
+            .then_some(ui_state.screenshot_include_ui);
 
         if available_rect.width() > 0.0 && available_rect.height() > 0.0 {
             self.render_shader(
                 ctx,
+                output_texture,
                 output_view,
                 controller,
                 available_rect * pixels_per_point,
             );
         }
+        #[cfg(not(target_arch = "wasm32"))]
+        if screenshot == Some(false) {
+            self.save_screenshot(ctx, output_texture, &ui_state.screenshot_dir);
+        }
 
         let screen_descriptor = egui_wgpu::ScreenDescriptor {
             size_in_pixels: [ctx.config.width, ctx.config.height],
@@ -257,6 +812,8 @@ impl RenderPass {
                 .update_texture(&ctx.device, &ctx.queue, *id, delta);
         }
 
+        // The shader may not have been rendered since the last resize
+        self.resize_attachments(ctx);
         let mut encoder = ctx
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -271,6 +828,9 @@ impl RenderPass {
             &screen_descriptor,
         );
 
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.begin(&mut encoder, GpuScope::Ui);
+        }
         {
             let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("UI Render Pass"),
@@ -285,7 +845,10 @@ impl RenderPass {
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: self
+                    .depth_target
+                    .as_ref()
+                    .map(DepthTarget::ui_attachment),
             });
 
             for id in &textures_delta.free {
@@ -298,31 +861,254 @@ impl RenderPass {
                 &screen_descriptor,
             );
         }
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.end(&mut encoder, GpuScope::Ui);
+        }
+
+        {
+            let _span = ctx.trace_span("submit");
+            ctx.queue.submit(Some(encoder.finish()));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if screenshot == Some(true) {
+            self.save_screenshot(ctx, output_texture, &ui_state.screenshot_dir);
+        }
+    }
+
+    /// Create or drop the profiler as the timings window is toggled or a trace is recorded,
+    /// and pass its finished measurements on to the trace
+    pub(crate) fn update_gpu_profiler(&mut self, ctx: &GraphicsContext, show_timings: bool) {
+        let trace = ctx.trace.as_ref().filter(|trace| trace.is_recording());
+        if !show_timings && trace.is_none() {
+            self.gpu_profiler = None;
+        } else if self.gpu_profiler.is_none() {
+            self.gpu_profiler = GpuProfiler::new(&ctx.device, &ctx.queue);
+        }
+        if let Some(profiler) = &mut self.gpu_profiler
+            && let Some(frame) = profiler.poll(&ctx.device)
+            && let Some(trace) = trace
+        {
+            trace.gpu_frame(&frame);
+        }
+    }
 
+    /// Start reading back the profiled frame. Called once the frame is over, after `update`,
+    /// so the compute scope is part of the measurement.
+    pub(crate) fn finish_gpu_profiling(&mut self, ctx: &GraphicsContext) {
+        let Some(profiler) = &mut self.gpu_profiler else {
+            return;
+        };
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("GPU Profiler Encoder"),
+            });
+        profiler.finish_frame(&mut encoder);
         ctx.queue.submit(Some(encoder.finish()));
+        profiler.read_back();
+    }
+
+    /// Save the shader viewport region of `texture` as a PNG in `dir`
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_screenshot(
+        &self,
+        ctx: &GraphicsContext,
+        texture: &wgpu::Texture,
+        dir: &std::path::Path,
+    ) {
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            log::warn!("Screenshots are not supported by this surface");
+            return;
+        }
+        let texture_size = glam::uvec2(texture.width(), texture.height());
+        let origin = self
+            .shader_offset()
+            .floor()
+            .max(glam::Vec2::ZERO)
+            .as_uvec2();
+        let size = glam::vec2(self.shader_viewport.width(), self.shader_viewport.height())
+            .floor()
+            .max(glam::Vec2::ZERO)
+            .as_uvec2()
+            .min(texture_size.saturating_sub(origin));
+        if size.min_element() == 0 {
+            log::warn!("Nothing to screenshot, the shader viewport is empty");
+            return;
+        }
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("screenshot-{timestamp}.png"));
+        match crate::capture::read_texture(ctx, texture, origin, size)
+            .and_then(|rgba| crate::capture::save_png(&path, size, &rgba))
+        {
+            Ok(()) => log::info!("Saved screenshot to {}", path.display()),
+            Err(err) => log::error!("Failed to save screenshot: {err}"),
+        }
     }
 
     #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
     pub fn new_module(&mut self, ctx: &GraphicsContext, shader_path: &std::path::Path) {
+        let shader_bytes = std::fs::read(shader_path).unwrap();
+        self.module = create_shader_module(&ctx.device, &shader_bytes);
+        self.fragment_entry_points = spirv::fragment_entry_points(&shader_bytes);
+        #[cfg(feature = "compute")]
+        {
+            self.workgroup_sizes = reflect_workgroup_sizes(&shader_bytes, &self.compute_kernels);
+        }
+        // Keyed by the shader, so the old cache does not apply
+        if let Some(pipeline_cache) = &self.pipeline_cache {
+            let dir = pipeline_cache.dir().to_owned();
+            self.pipeline_cache = PipelineCache::new(ctx, &dir, &shader_bytes);
+        }
+        self.rebuild_pipelines(ctx);
+        if let Some(pipeline_cache) = &self.pipeline_cache {
+            pipeline_cache.save();
+        }
+    }
+
+    /// Fragment entry points reflected from the shader, empty if it could not be parsed
+    pub(crate) fn fragment_entry_points(&self) -> &[String] {
+        self.fragment_entry_points.as_deref().unwrap_or_default()
+    }
+
+    /// Switch to another fragment entry point of the final pass, only rebuilding its pipeline.
+    /// The current one is kept if the shader has no such entry point.
+    pub fn set_fragment_entry_point(
+        &mut self,
+        ctx: &GraphicsContext,
+        entry_point: &str,
+    ) -> Result<(), Error> {
+        if self.entry_points.fragment == entry_point {
+            return Ok(());
+        }
+        if let Some(names) = &self.fragment_entry_points
+            && !names.iter().any(|name| name == entry_point)
+        {
+            return Err(Error::MissingFragmentEntryPoint(entry_point.to_owned()));
+        }
+        self.pipelines.render = create_render_pipeline(
+            &ctx.device,
+            &self.pipeline_layouts.render,
+            &self.vertex_buffer_layouts,
+            self.depth_target.as_ref().map(|depth| &depth.state),
+            self.sample_count,
+            self.primitive,
+            &self.module,
+            &self.entry_points.vertex,
+            entry_point,
+            ctx.config.format,
+            self.blend,
+            self.pipeline_cache(),
+        );
+        self.entry_points.fragment = entry_point.to_owned();
+        Ok(())
+    }
+
+    fn rebuild_pipelines(&mut self, ctx: &GraphicsContext) {
         self.pipelines = create_pipelines(
             &ctx.device,
             &self.pipeline_layouts,
             ctx.config.format,
             &self.vertex_buffer_layouts,
-            &std::fs::read(shader_path).unwrap(),
+            self.depth_target.as_ref().map(|depth| &depth.state),
+            self.sample_count,
+            self.primitive,
+            self.blend,
+            &self.shader_passes,
+            #[cfg(feature = "compute")]
+            &self.compute_kernels,
+            &self.module,
+            &self.entry_points,
+            self.pipeline_cache(),
         );
     }
 
+    fn pipeline_cache(&self) -> Option<&wgpu::PipelineCache> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.pipeline_cache.as_ref().map(PipelineCache::cache);
+        #[cfg(target_arch = "wasm32")]
+        None
+    }
+
+    /// `position` is relative to the shader viewport
+    pub fn mouse_move(&mut self, position: glam::Vec2) {
+        if let Some(standard_uniforms) = &mut self.standard_uniforms {
+            standard_uniforms.mouse_move(position);
+        }
+    }
+
+    pub fn mouse_input(
+        &mut self,
+        state: egui_winit::winit::event::ElementState,
+        button: egui_winit::winit::event::MouseButton,
+    ) {
+        if let Some(standard_uniforms) = &mut self.standard_uniforms {
+            standard_uniforms.mouse_input(state, button);
+        }
+    }
+
     pub fn shader_offset(&self) -> glam::Vec2 {
         glam::vec2(self.shader_viewport.left(), self.shader_viewport.top())
     }
 }
 
+/// Check that `format` can be rendered with `sample_count` samples and, for color targets,
+/// resolved
+fn check_sample_count(
+    ctx: &GraphicsContext,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    resolve: bool,
+) -> Result<(), Error> {
+    let flags = ctx.texture_format_features(format).flags;
+    let supported = sample_count == 1
+        || (flags.sample_count_supported(sample_count)
+            && (!resolve || flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)));
+    if !supported {
+        return Err(Error::UnsupportedSampleCount(sample_count, format));
+    }
+    Ok(())
+}
+
+/// Check that push constants of `size` bytes fit the device, or the emulation buffer
+fn check_push_constant_size(device: &wgpu::Device, name: &str, size: u32) -> Result<(), Error> {
+    let limits = device.limits();
+    let limit = if cfg!(feature = "emulate_constants") {
+        limits.max_storage_buffer_binding_size
+    } else {
+        limits.max_push_constant_size
+    };
+    if size > limit {
+        return Err(Error::PushConstantsTooLarge {
+            name: name.to_owned(),
+            size,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(feature = "compute")]
+fn reflect_workgroup_sizes(
+    shader_bytes: &[u8],
+    compute_kernels: &[ComputeKernel],
+) -> Vec<Option<glam::UVec3>> {
+    compute_kernels
+        .iter()
+        .map(|kernel| reflect_workgroup_size(shader_bytes, &kernel.entry_point))
+        .collect()
+}
+
 fn create_pipeline_layouts(
     ctx: &GraphicsContext,
     bind_group_layouts: &[&wgpu::BindGroupLayout],
+    #[cfg_attr(feature = "emulate_constants", allow(unused_variables))]
+    render_push_constant_size: u32,
+    #[cfg(feature = "compute")] compute_kernels: &[ComputeKernel],
 ) -> PipelineLayouts {
-    let create = |push_constant_ranges| {
+    let create = |push_constant_ranges: &[wgpu::PushConstantRange]| {
         ctx.device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
@@ -330,93 +1116,150 @@ fn create_pipeline_layouts(
                 push_constant_ranges,
             })
     };
+    // Emulated constants and empty ranges need no push constant range
+    let create_with = |stages, size: u32| {
+        if cfg!(feature = "emulate_constants") || size == 0 {
+            create(&[])
+        } else {
+            create(&[wgpu::PushConstantRange {
+                stages,
+                range: 0..size,
+            }])
+        }
+    };
     PipelineLayouts {
-        render: create(&[
-            #[cfg(not(feature = "emulate_constants"))]
-            wgpu::PushConstantRange {
-                stages: wgpu::ShaderStages::FRAGMENT,
-                range: 0..128,
-            },
-        ]),
+        render: create_with(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            render_push_constant_size,
+        ),
         #[cfg(feature = "compute")]
-        compute: create(&[
-            #[cfg(not(feature = "emulate_constants"))]
-            wgpu::PushConstantRange {
-                stages: wgpu::ShaderStages::COMPUTE,
-                range: 0..128,
-            },
-        ]),
+        compute: compute_kernels
+            .iter()
+            .map(|kernel| create_with(wgpu::ShaderStages::COMPUTE, kernel.push_constant_size))
+            .collect(),
     }
 }
 
+fn create_shader_module(device: &wgpu::Device, shader_bytes: &[u8]) -> wgpu::ShaderModule {
+    let spirv = wgpu::util::make_spirv(shader_bytes);
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: spirv,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
 fn create_pipelines(
     device: &wgpu::Device,
     pipeline_layouts: &PipelineLayouts,
     surface_format: wgpu::TextureFormat,
     vertex_buffer_layouts: &[wgpu::VertexBufferLayout],
-    shader_bytes: &[u8],
+    depth_stencil: Option<&wgpu::DepthStencilState>,
+    sample_count: u32,
+    primitive: wgpu::PrimitiveState,
+    blend: Option<wgpu::BlendState>,
+    shader_passes: &[ShaderPass],
+    #[cfg(feature = "compute")] compute_kernels: &[ComputeKernel],
+    module: &wgpu::ShaderModule,
+    entry_points: &EntryPoints,
+    cache: Option<&wgpu::PipelineCache>,
 ) -> Pipelines {
-    let spirv = wgpu::util::make_spirv(shader_bytes);
-    let module = &device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: None,
-        source: spirv,
-    });
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    let create_render_pipeline = |fragment_entry_point: &str, format, blend| {
+        create_render_pipeline(
+            device,
+            &pipeline_layouts.render,
+            vertex_buffer_layouts,
+            depth_stencil,
+            sample_count,
+            primitive,
+            module,
+            &entry_points.vertex,
+            fragment_entry_point,
+            format,
+            blend,
+            cache,
+        )
+    };
+    let render_pipeline = create_render_pipeline(&entry_points.fragment, surface_format, blend);
+    // Passes replace the contents of their targets
+    let pass_pipelines = shader_passes
+        .iter()
+        .map(|pass| create_render_pipeline(&pass.fragment_entry_point, pass.format, None))
+        .collect();
+    #[cfg(feature = "compute")]
+    let compute_pipelines = compute_kernels
+        .iter()
+        .zip(&pipeline_layouts.compute)
+        .map(|(kernel, layout)| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&kernel.entry_point),
+                layout: Some(layout),
+                module,
+                entry_point: Some(&kernel.entry_point),
+                compilation_options: Default::default(),
+                cache,
+            })
+        })
+        .collect();
+    Pipelines {
+        render: render_pipeline,
+        passes: pass_pipelines,
+        #[cfg(feature = "compute")]
+        compute: compute_pipelines,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vertex_buffer_layouts: &[wgpu::VertexBufferLayout],
+    depth_stencil: Option<&wgpu::DepthStencilState>,
+    sample_count: u32,
+    primitive: wgpu::PrimitiveState,
+    module: &wgpu::ShaderModule,
+    vertex_entry_point: &str,
+    fragment_entry_point: &str,
+    format: wgpu::TextureFormat,
+    blend: Option<wgpu::BlendState>,
+    cache: Option<&wgpu::PipelineCache>,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: None,
-        layout: Some(&pipeline_layouts.render),
+        layout: Some(layout),
         vertex: wgpu::VertexState {
             module,
-            entry_point: Some("main_vs"),
+            entry_point: Some(vertex_entry_point),
             buffers: vertex_buffer_layouts,
             compilation_options: Default::default(),
         },
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: None,
-            unclipped_depth: false,
-            polygon_mode: wgpu::PolygonMode::Fill,
-            conservative: false,
-        },
-        depth_stencil: None,
+        primitive,
+        depth_stencil: depth_stencil.cloned(),
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
         fragment: Some(wgpu::FragmentState {
             module,
-            entry_point: Some("main_fs"),
+            entry_point: Some(fragment_entry_point),
             targets: &[Some(wgpu::ColorTargetState {
-                format: surface_format,
-                blend: None,
+                format,
+                blend,
                 write_mask: wgpu::ColorWrites::ALL,
             })],
             compilation_options: Default::default(),
         }),
         multiview: None,
-        cache: None,
-    });
-    #[cfg(feature = "compute")]
-    let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: None,
-        layout: Some(&pipeline_layouts.compute),
-        module,
-        entry_point: Some("main_cs"),
-        compilation_options: Default::default(),
-        cache: None,
-    });
-    Pipelines {
-        render: render_pipeline,
-        #[cfg(feature = "compute")]
-        compute: compute_pipeline,
-    }
+        cache,
+    })
 }
 
 #[cfg(feature = "emulate_constants")]
 fn create_emulate_constants_bind_groups(
     device: &wgpu::Device,
+    render_size: u32,
+    #[cfg(feature = "compute")] compute_size: u32,
 ) -> (
     wgpu::BindGroupLayout,
     wgpu::BindGroup,
@@ -426,7 +1269,7 @@ fn create_emulate_constants_bind_groups(
         entries: &[
             wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Storage { read_only: true },
                     has_dynamic_offset: false,
@@ -440,7 +1283,7 @@ fn create_emulate_constants_bind_groups(
                 visibility: wgpu::ShaderStages::COMPUTE,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
+                    has_dynamic_offset: true,
                     min_binding_size: None,
                 },
                 count: None,
@@ -448,19 +1291,24 @@ fn create_emulate_constants_bind_groups(
         ],
         label: Some("emulated push constants layout"),
     });
-    let usage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
-    use wgpu::util::DeviceExt;
-    let fragment_constants_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: &[0; 128],
-        usage,
-    });
+    let create_buffer = |size: u32| {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: size as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    };
+    // Bindings cannot be empty
+    let render_size = render_size.max(wgpu::PUSH_CONSTANT_ALIGNMENT);
+    let fragment_constants_buffer = create_buffer(render_size);
     #[cfg(feature = "compute")]
-    let compute_constants_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: &[0; 128],
-        usage,
-    });
+    let compute_size = compute_size.max(wgpu::PUSH_CONSTANT_ALIGNMENT);
+    #[cfg(feature = "compute")]
+    let compute_stride =
+        compute_size.next_multiple_of(device.limits().min_storage_buffer_offset_alignment);
+    #[cfg(feature = "compute")]
+    let compute_constants_buffer = create_buffer(compute_stride * EMULATED_DISPATCHES_PER_SUBMIT);
     let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
         layout: &layout,
         entries: &[
@@ -471,7 +1319,11 @@ fn create_emulate_constants_bind_groups(
             #[cfg(feature = "compute")]
             wgpu::BindGroupEntry {
                 binding: 1,
-                resource: compute_constants_buffer.as_entire_binding(),
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &compute_constants_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(compute_size as wgpu::BufferAddress),
+                }),
             },
         ],
         label: Some("emulated push constants bind group"),
@@ -483,6 +1335,8 @@ fn create_emulate_constants_bind_groups(
             render: fragment_constants_buffer,
             #[cfg(feature = "compute")]
             compute: compute_constants_buffer,
+            #[cfg(feature = "compute")]
+            compute_stride,
         },
     )
 }
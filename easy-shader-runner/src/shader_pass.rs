@@ -0,0 +1,220 @@
+use crate::{Error, color_target::ColorTarget};
+use glam::UVec2;
+
+/// An offscreen fragment pass, like a Shadertoy buffer.
+///
+/// Passes run in order before the final pass that draws to the screen. Each pass renders
+/// into its own texture the size of the surface, using the same viewport and push constants
+/// as the final pass.
+///
+/// The outputs are bound as one extra bind group, directly after the controller's own bind
+/// groups (and after the standard uniforms if enabled): binding 0 is a sampler and binding `1 + i` is the output of pass `i`.
+/// Declared passes see the output of the previous frame, so a pass can read its own
+/// previous output. The final pass and compute shaders see the output of the current frame.
+#[derive(Clone, Debug)]
+pub struct ShaderPass {
+    pub fragment_entry_point: String,
+    pub format: wgpu::TextureFormat,
+}
+
+impl ShaderPass {
+    pub fn new(fragment_entry_point: impl Into<String>) -> Self {
+        Self {
+            fragment_entry_point: fragment_entry_point.into(),
+            format: wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+
+    pub fn format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+/// Ping-pong render targets for the declared [`ShaderPass`]es
+pub(crate) struct PassTargets {
+    pub layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    size: UVec2,
+    /// Indexed by `[parity][pass]`
+    views: [Vec<wgpu::TextureView>; 2],
+    bind_groups: [wgpu::BindGroup; 2],
+    /// One per pass when multisampling, resolved into `views`
+    multisample: Vec<ColorTarget>,
+    /// Which half of `views` is written this frame
+    parity: usize,
+}
+
+impl PassTargets {
+    pub fn new(
+        device: &wgpu::Device,
+        passes: &[ShaderPass],
+        sample_count: u32,
+        size: UVec2,
+    ) -> Result<Self, Error> {
+        let sample_types = passes
+            .iter()
+            .map(|pass| {
+                pass.format
+                    .sample_type(None, Some(device.features()))
+                    .ok_or(Error::UnsampleablePassFormat(pass.format))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let filterable = sample_types
+            .iter()
+            .all(|ty| matches!(ty, wgpu::TextureSampleType::Float { filterable: true }));
+        let visibility = wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE;
+        let entries = std::iter::once(wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Sampler(if filterable {
+                wgpu::SamplerBindingType::Filtering
+            } else {
+                wgpu::SamplerBindingType::NonFiltering
+            }),
+            count: None,
+        })
+        .chain(
+            sample_types
+                .into_iter()
+                .enumerate()
+                .map(|(i, sample_type)| wgpu::BindGroupLayoutEntry {
+                    binding: 1 + i as u32,
+                    visibility,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }),
+        )
+        .collect::<Vec<_>>();
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("shader pass targets layout"),
+            entries: &entries,
+        });
+        let filter = if filterable {
+            wgpu::FilterMode::Linear
+        } else {
+            wgpu::FilterMode::Nearest
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shader pass sampler"),
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+        let (views, bind_groups) = create_targets(device, passes, size, &layout, &sampler);
+        let multisample = if sample_count > 1 {
+            passes
+                .iter()
+                .map(|pass| ColorTarget::new(device, pass.format, sample_count, size))
+                .collect()
+        } else {
+            vec![]
+        };
+        Ok(Self {
+            layout,
+            sampler,
+            size,
+            views,
+            bind_groups,
+            multisample,
+            parity: 0,
+        })
+    }
+
+    /// Recreate the targets if the surface size changed. Previous contents are lost.
+    pub fn resize(&mut self, device: &wgpu::Device, passes: &[ShaderPass], size: UVec2) {
+        if size != self.size {
+            (self.views, self.bind_groups) =
+                create_targets(device, passes, size, &self.layout, &self.sampler);
+            for target in &mut self.multisample {
+                target.resize(device, size);
+            }
+            self.size = size;
+        }
+    }
+
+    /// Render target of pass `i` for this frame and its resolve target when multisampling
+    pub fn output_views(&self, i: usize) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        let view = &self.views[self.parity][i];
+        match self.multisample.get(i) {
+            Some(target) => (target.view(), Some(view)),
+            None => (view, None),
+        }
+    }
+
+    /// Outputs of the previous frame
+    pub fn previous_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_groups[1 - self.parity]
+    }
+
+    /// Outputs of the current frame
+    pub fn current_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_groups[self.parity]
+    }
+
+    /// Called at the start of each frame, before its passes are recorded, so the previous
+    /// frame's outputs become [`Self::previous_bind_group`]
+    pub fn swap(&mut self) {
+        self.parity = 1 - self.parity;
+    }
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    passes: &[ShaderPass],
+    size: UVec2,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+) -> ([Vec<wgpu::TextureView>; 2], [wgpu::BindGroup; 2]) {
+    let views = [0, 1].map(|parity| {
+        passes
+            .iter()
+            .enumerate()
+            .map(|(i, pass)| {
+                device
+                    .create_texture(&wgpu::TextureDescriptor {
+                        label: Some(&format!("shader pass {i} target {parity}")),
+                        size: wgpu::Extent3d {
+                            width: size.x.max(1),
+                            height: size.y.max(1),
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: pass.format,
+                        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                            | wgpu::TextureUsages::TEXTURE_BINDING,
+                        view_formats: &[],
+                    })
+                    .create_view(&wgpu::TextureViewDescriptor::default())
+            })
+            .collect::<Vec<_>>()
+    });
+    let bind_groups = [0, 1].map(|parity| {
+        let entries = std::iter::once(wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Sampler(sampler),
+        })
+        .chain(
+            views[parity]
+                .iter()
+                .enumerate()
+                .map(|(i, view)| wgpu::BindGroupEntry {
+                    binding: 1 + i as u32,
+                    resource: wgpu::BindingResource::TextureView(view),
+                }),
+        )
+        .collect::<Vec<_>>();
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shader pass targets bind group"),
+            layout,
+            entries: &entries,
+        })
+    });
+    (views, bind_groups)
+}
@@ -0,0 +1,170 @@
+pub(crate) const EXECUTION_MODEL_FRAGMENT: u32 = 4;
+#[cfg(feature = "compute")]
+pub(crate) const EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+
+const MAGIC: u32 = 0x0723_0203;
+const OP_ENTRY_POINT: u32 = 15;
+
+/// Just enough of a SPIR-V module to reflect what the runner needs from the shader
+pub(crate) struct SpirvModule {
+    /// Instructions after the 5 word header
+    words: Vec<u32>,
+}
+
+impl SpirvModule {
+    /// `None` if `spirv` is not a well-formed module
+    pub fn parse(spirv: &[u8]) -> Option<Self> {
+        if !spirv.len().is_multiple_of(4) {
+            return None;
+        }
+        let words: Vec<u32> = spirv
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        if words.first() != Some(&MAGIC) {
+            return None;
+        }
+        let words = words.get(5..)?.to_vec();
+        let mut rest = &words[..];
+        while let Some(&first) = rest.first() {
+            let count = (first >> 16) as usize;
+            if count == 0 || count > rest.len() {
+                return None;
+            }
+            rest = &rest[count..];
+        }
+        Some(Self { words })
+    }
+
+    /// Opcode and operands of each instruction
+    pub fn instructions(&self) -> impl Iterator<Item = (u32, &[u32])> {
+        let mut rest = &self.words[..];
+        std::iter::from_fn(move || {
+            let count = (*rest.first()? >> 16) as usize;
+            let (instruction, next) = rest.split_at(count);
+            rest = next;
+            Some((instruction[0] & 0xffff, &instruction[1..]))
+        })
+    }
+
+    /// Id of the entry point called `name` with `execution_model`
+    #[cfg(feature = "compute")]
+    pub fn entry_point(&self, execution_model: u32, name: &str) -> Option<u32> {
+        self.entry_points(execution_model)
+            .find_map(|(id, entry_point)| (entry_point == name).then_some(id))
+    }
+
+    /// Id and name of each entry point with `execution_model`
+    pub fn entry_points(&self, execution_model: u32) -> impl Iterator<Item = (u32, String)> {
+        self.instructions()
+            .filter_map(move |(opcode, operands)| match operands {
+                [model, id, name @ ..] if opcode == OP_ENTRY_POINT && *model == execution_model => {
+                    let name: Vec<u8> = name
+                        .iter()
+                        .flat_map(|word| word.to_le_bytes())
+                        .take_while(|&byte| byte != 0)
+                        .collect();
+                    Some((*id, String::from_utf8_lossy(&name).into_owned()))
+                }
+                _ => None,
+            })
+    }
+}
+
+/// Names of the fragment entry points, `None` if `spirv` is not a well-formed module
+pub(crate) fn fragment_entry_points(spirv: &[u8]) -> Option<Vec<String>> {
+    let module = SpirvModule::parse(spirv)?;
+    Some(
+        module
+            .entry_points(EXECUTION_MODEL_FRAGMENT)
+            .map(|(_, name)| name)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A module with a GLCompute entry point `main` of 16x8x1 threads and a Fragment entry
+    /// point `frag`
+    pub(crate) fn module() -> Vec<u8> {
+        let name = |name: &[u8; 8]| {
+            [
+                u32::from_le_bytes(name[..4].try_into().unwrap()),
+                u32::from_le_bytes(name[4..].try_into().unwrap()),
+            ]
+        };
+        let [main_0, main_1] = name(b"main\0\0\0\0");
+        let [frag_0, frag_1] = name(b"frag\0\0\0\0");
+        let words = [
+            // Header: magic, version 1.0, generator, bound, schema
+            0x0723_0203,
+            0x0001_0000,
+            0,
+            3,
+            0,
+            // OpEntryPoint GLCompute %1 "main"
+            (5 << 16) | 15,
+            5,
+            1,
+            main_0,
+            main_1,
+            // OpEntryPoint Fragment %2 "frag"
+            (5 << 16) | 15,
+            4,
+            2,
+            frag_0,
+            frag_1,
+            // OpExecutionMode %1 LocalSize 16 8 1
+            (6 << 16) | 16,
+            1,
+            17,
+            16,
+            8,
+            1,
+        ];
+        words
+            .iter()
+            .flat_map(|word: &u32| word.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn lists_entry_points_by_execution_model() {
+        let module = SpirvModule::parse(&module()).unwrap();
+        assert_eq!(
+            module
+                .entry_points(EXECUTION_MODEL_FRAGMENT)
+                .collect::<Vec<_>>(),
+            [(2, "frag".to_owned())]
+        );
+        assert_eq!(
+            // GLCompute
+            module.entry_points(5).collect::<Vec<_>>(),
+            [(1, "main".to_owned())]
+        );
+    }
+
+    #[test]
+    fn reflects_fragment_entry_points() {
+        assert_eq!(
+            fragment_entry_points(&module()),
+            Some(vec!["frag".to_owned()])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_modules() {
+        let module = module();
+        for bytes in [
+            &module[..module.len() - 4],
+            &module[..module.len() - 1],
+            &module[4..],
+            &[],
+        ] {
+            assert!(SpirvModule::parse(bytes).is_none());
+            assert_eq!(fragment_entry_points(bytes), None);
+        }
+    }
+}
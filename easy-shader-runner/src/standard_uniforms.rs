@@ -0,0 +1,85 @@
+use crate::clock::Clock;
+use easy_shader_runner_shared::StandardUniforms;
+use egui_winit::winit::event::{ElementState, MouseButton};
+use glam::Vec2;
+
+/// Uniform buffer holding [`StandardUniforms`], written once per frame
+pub(crate) struct StandardUniformsBuffer {
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    buffer: wgpu::Buffer,
+    cursor: Vec2,
+    buttons: u32,
+}
+
+impl StandardUniformsBuffer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("standard uniforms layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("standard uniforms"),
+            size: std::mem::size_of::<StandardUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("standard uniforms bind group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            layout,
+            bind_group,
+            buffer,
+            cursor: Vec2::ZERO,
+            buttons: 0,
+        }
+    }
+
+    /// `position` is relative to the shader viewport
+    pub fn mouse_move(&mut self, position: Vec2) {
+        self.cursor = position;
+    }
+
+    pub fn mouse_input(&mut self, state: ElementState, button: MouseButton) {
+        let bit = match button {
+            MouseButton::Left => 0,
+            MouseButton::Middle => 1,
+            MouseButton::Right => 2,
+            MouseButton::Back => 3,
+            MouseButton::Forward => 4,
+            MouseButton::Other(_) => return,
+        };
+        match state {
+            ElementState::Pressed => self.buttons |= 1 << bit,
+            ElementState::Released => self.buttons &= !(1 << bit),
+        }
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue, clock: &Clock, resolution: Vec2, offset: Vec2) {
+        // Padding is private to the shared crate
+        let mut uniforms = StandardUniforms::default();
+        uniforms.resolution = resolution;
+        uniforms.offset = offset;
+        uniforms.cursor = self.cursor;
+        uniforms.time = clock.elapsed().as_secs_f32();
+        uniforms.delta = clock.delta().as_secs_f32();
+        uniforms.frame = clock.frame() as u32;
+        uniforms.buttons = self.buttons;
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::gpu_profiler::GpuFrame;
+use std::{
+    path::PathBuf,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use web_time::Instant;
+
+/// Path to record a trace to, takes precedence over [`crate::Parameters::trace`]
+const PATH_VAR: &str = "EASY_SHADER_RUNNER_TRACE";
+/// Number of frames to record when the trace is enabled through [`PATH_VAR`]
+const FRAMES_VAR: &str = "EASY_SHADER_RUNNER_TRACE_FRAMES";
+const DEFAULT_FRAMES: u32 = 300;
+
+const CPU_THREAD: u32 = 1;
+const GPU_THREAD: u32 = 2;
+
+struct Event {
+    name: &'static str,
+    thread: u32,
+    /// Microseconds since the trace started
+    start: f64,
+    duration: f64,
+}
+
+struct State {
+    events: Vec<Event>,
+    frames: u32,
+    frame_start: f64,
+    /// Added to GPU timestamps to place them on the CPU timeline
+    gpu_offset: Option<f64>,
+}
+
+/// Records CPU and GPU spans of the first frames and writes them as a Chrome trace JSON
+/// file, which can be opened in Perfetto or `chrome://tracing`.
+///
+/// GPU spans are aligned with the CPU timeline once, at the first measurement, so only their
+/// durations and the distances between them are exact.
+pub(crate) struct Trace {
+    path: PathBuf,
+    max_frames: u32,
+    start: Instant,
+    finished: AtomicBool,
+    state: Mutex<State>,
+}
+
+impl Trace {
+    /// Configured from the environment if set, otherwise from `options`
+    pub fn new(options: Option<(PathBuf, u32)>) -> Option<Self> {
+        let (path, max_frames) = match std::env::var_os(PATH_VAR) {
+            Some(path) => {
+                let frames = std::env::var(FRAMES_VAR)
+                    .ok()
+                    .and_then(|frames| frames.parse().ok())
+                    .unwrap_or(DEFAULT_FRAMES);
+                (PathBuf::from(path), frames)
+            }
+            None => options?,
+        };
+        Some(Self {
+            path,
+            max_frames,
+            start: Instant::now(),
+            finished: AtomicBool::new(false),
+            state: Mutex::new(State {
+                events: Vec::new(),
+                frames: 0,
+                frame_start: 0.0,
+                gpu_offset: None,
+            }),
+        })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        !self.finished.load(Ordering::Relaxed)
+    }
+
+    /// Start a CPU span that ends when the returned guard is dropped
+    pub fn span(&self, name: &'static str) -> TraceSpan<'_> {
+        TraceSpan {
+            trace: self,
+            name,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn gpu_frame(&self, frame: &GpuFrame) {
+        let Some(&(_, first_begin, _)) = frame.spans.first() else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let offset = *state
+            .gpu_offset
+            .get_or_insert_with(|| self.micros(frame.cpu_start) - first_begin * 1e-3);
+        for &(name, begin, end) in &frame.spans {
+            state.events.push(Event {
+                name,
+                thread: GPU_THREAD,
+                start: begin * 1e-3 + offset,
+                duration: (end - begin).max(0.0) * 1e-3,
+            });
+        }
+    }
+
+    /// Record a span for the whole frame and write the trace after the last one
+    pub fn end_frame(&self) {
+        if !self.is_recording() {
+            return;
+        }
+        let now = self.micros(Instant::now());
+        let mut state = self.state.lock().unwrap();
+        let frame_start = std::mem::replace(&mut state.frame_start, now);
+        state.events.push(Event {
+            name: "frame",
+            thread: CPU_THREAD,
+            start: frame_start,
+            duration: now - frame_start,
+        });
+        state.frames += 1;
+        if state.frames >= self.max_frames {
+            self.finish(&mut state);
+        }
+    }
+
+    /// Stop recording and write the trace
+    fn finish(&self, state: &mut State) {
+        self.finished.store(true, Ordering::Relaxed);
+        let events = std::mem::take(&mut state.events);
+        match std::fs::write(&self.path, to_json(&events)) {
+            Ok(()) => log::info!(
+                "Wrote trace of {} frames to {}",
+                state.frames,
+                self.path.display()
+            ),
+            Err(err) => log::error!("Failed to write trace to {}: {err}", self.path.display()),
+        }
+    }
+
+    fn micros(&self, instant: Instant) -> f64 {
+        instant.saturating_duration_since(self.start).as_secs_f64() * 1e6
+    }
+}
+
+impl Drop for Trace {
+    /// Write what was recorded if the run ends before the last frame
+    fn drop(&mut self) {
+        if self.is_recording() {
+            let mut state = self.state.lock().unwrap();
+            self.finish(&mut state);
+        }
+    }
+}
+
+/// CPU span of a [`Trace`], recorded when dropped
+pub(crate) struct TraceSpan<'a> {
+    trace: &'a Trace,
+    name: &'static str,
+    start: Instant,
+}
+
+impl Drop for TraceSpan<'_> {
+    fn drop(&mut self) {
+        if !self.trace.is_recording() {
+            return;
+        }
+        let start = self.trace.micros(self.start);
+        let end = self.trace.micros(Instant::now());
+        self.trace.state.lock().unwrap().events.push(Event {
+            name: self.name,
+            thread: CPU_THREAD,
+            start,
+            duration: end - start,
+        });
+    }
+}
+
+/// Event names are identifiers, so nothing needs escaping
+fn to_json(events: &[Event]) -> String {
+    let metadata = [(CPU_THREAD, "CPU"), (GPU_THREAD, "GPU")].map(|(thread, name)| {
+        format!(
+            "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{thread},\"args\":{{\"name\":\"{name}\"}}}}"
+        )
+    });
+    let spans = events.iter().map(|event| {
+        format!(
+            "{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{:.3},\"dur\":{:.3}}}",
+            event.name, event.thread, event.start, event.duration
+        )
+    });
+    let events = metadata.into_iter().chain(spans).collect::<Vec<_>>();
+    format!(
+        "{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ms\"}}\n",
+        events.join(",\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_json_writes_complete_events() {
+        let events = [
+            Event {
+                name: "frame",
+                thread: CPU_THREAD,
+                start: 0.0,
+                duration: 16666.6667,
+            },
+            Event {
+                name: "shader",
+                thread: GPU_THREAD,
+                start: 1000.5,
+                duration: 250.25,
+            },
+        ];
+        assert_eq!(
+            to_json(&events),
+            r#"{"traceEvents":[
+{"name":"thread_name","ph":"M","pid":1,"tid":1,"args":{"name":"CPU"}},
+{"name":"thread_name","ph":"M","pid":1,"tid":2,"args":{"name":"GPU"}},
+{"name":"frame","ph":"X","pid":1,"tid":1,"ts":0.000,"dur":16666.667},
+{"name":"shader","ph":"X","pid":1,"tid":2,"ts":1000.500,"dur":250.250}
+],"displayTimeUnit":"ms"}
+"#
+        );
+    }
+
+    #[test]
+    fn gpu_frame_records_every_scope() {
+        let path = std::env::temp_dir().join(format!("esr-trace-{}.json", std::process::id()));
+        let trace = Trace::new(Some((path.clone(), 1))).unwrap();
+        // What the profiler reads back for a windowed frame with a compute update
+        trace.gpu_frame(&GpuFrame {
+            cpu_start: Instant::now(),
+            spans: vec![
+                ("compute", 0.0, 1000.0),
+                ("shader", 1000.0, 3000.0),
+                ("ui", 3000.0, 3500.0),
+            ],
+        });
+        trace.end_frame();
+        let json = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        for (name, duration) in [("compute", "1.000"), ("shader", "2.000"), ("ui", "0.500")] {
+            assert!(
+                json.lines()
+                    .any(|line| line.starts_with(&format!("{{\"name\":\"{name}\""))
+                        && line.contains("\"tid\":2")
+                        && line.contains(&format!("\"dur\":{duration}}}"))),
+                "missing GPU span {name} in {json}"
+            );
+        }
+    }
+
+    #[test]
+    fn to_json_without_events_only_names_threads() {
+        assert_eq!(
+            to_json(&[]),
+            r#"{"traceEvents":[
+{"name":"thread_name","ph":"M","pid":1,"tid":1,"args":{"name":"CPU"}},
+{"name":"thread_name","ph":"M","pid":1,"tid":2,"args":{"name":"GPU"}}
+],"displayTimeUnit":"ms"}
+"#
+        );
+    }
+}
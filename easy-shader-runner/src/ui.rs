@@ -1,21 +1,52 @@
-use crate::{GraphicsContext, controller::ControllerTrait, fps_counter::FpsCounter};
+use crate::{
+    EntryPoints, GraphicsContext, controller::ControllerTrait, fps_counter::FpsCounter,
+    gpu_profiler::GpuTimings,
+};
 use egui::{
     Context,
     epaint::{ClippedPrimitive, textures::TexturesDelta},
 };
+#[cfg(not(target_arch = "wasm32"))]
+use egui_winit::winit::keyboard::NamedKey;
 use egui_winit::{
     State,
     winit::{event::WindowEvent, window::Window},
 };
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
 use std::sync::Arc;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Options {
     pub escape_exits: bool,
+    pub entry_points: EntryPoints,
+    pub sample_count: u32,
+    pub standard_uniforms: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub screenshot_key: Option<NamedKey>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub screenshot_dir: PathBuf,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub pipeline_cache_dir: Option<PathBuf>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub trace: Option<(PathBuf, u32)>,
 }
 impl Default for Options {
     fn default() -> Self {
-        Self { escape_exits: true }
+        Self {
+            escape_exits: true,
+            entry_points: EntryPoints::default(),
+            sample_count: 1,
+            standard_uniforms: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            screenshot_key: Some(NamedKey::F12),
+            #[cfg(not(target_arch = "wasm32"))]
+            screenshot_dir: PathBuf::from("."),
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline_cache_dir: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            trace: None,
+        }
     }
 }
 
@@ -26,6 +57,25 @@ pub struct UiState {
     pub fullscreen: bool,
     pub(crate) fullscreen_set: bool,
     pub escape_exits: bool,
+    /// Fragment entry point of the final pass. Changing it rebuilds the render pipeline.
+    pub fragment_entry_point: String,
+    /// Filled in by the runner whenever the shader module is (re)loaded
+    pub(crate) fragment_entry_points: Vec<String>,
+    /// Show a window with the GPU time of each part of the frame. Measuring is only done
+    /// while it is shown.
+    pub show_gpu_timings: bool,
+    /// `None` if the adapter lacks `TIMESTAMP_QUERY` or measuring is off
+    pub(crate) gpu_timings: Option<GpuTimings>,
+    /// Save a PNG of the shader viewport at the end of the next frame
+    #[cfg(not(target_arch = "wasm32"))]
+    pub screenshot_requested: bool,
+    /// Whether screenshots include the egui overlay
+    #[cfg(not(target_arch = "wasm32"))]
+    pub screenshot_include_ui: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub screenshot_key: Option<NamedKey>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub screenshot_dir: PathBuf,
 }
 
 impl UiState {
@@ -37,6 +87,18 @@ impl UiState {
             fullscreen: false,
             fullscreen_set: false,
             escape_exits: options.escape_exits,
+            fragment_entry_point: options.entry_points.fragment,
+            fragment_entry_points: Vec::new(),
+            show_gpu_timings: false,
+            gpu_timings: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            screenshot_requested: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            screenshot_include_ui: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            screenshot_key: options.screenshot_key,
+            #[cfg(not(target_arch = "wasm32"))]
+            screenshot_dir: options.screenshot_dir,
         }
     }
 
@@ -47,6 +109,23 @@ impl UiState {
     pub fn is_fullscreen(&self) -> bool {
         self.fullscreen_set
     }
+
+    /// Fragment entry points of the shader, to choose [`Self::fragment_entry_point`] from.
+    /// Empty if they could not be read from the shader.
+    pub fn fragment_entry_points(&self) -> &[String] {
+        &self.fragment_entry_points
+    }
+
+    /// Latest GPU timings while [`Self::show_gpu_timings`] is set
+    pub fn gpu_timings(&self) -> Option<&GpuTimings> {
+        self.gpu_timings.as_ref()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn request_screenshot(&mut self, include_ui: bool) {
+        self.screenshot_requested = true;
+        self.screenshot_include_ui = include_ui;
+    }
 }
 
 impl Default for UiState {
@@ -97,15 +176,18 @@ impl Ui {
         let raw_input = self.egui_winit_state.take_egui_input(window);
         let mut available_rect = egui::Rect::NAN;
         let full_output = self.egui_winit_state.egui_ctx().run(raw_input, |ctx| {
+            let _span = graphics_context.trace_span("ui");
             self.ui(ctx, ui_state, controller, graphics_context);
             available_rect = ctx.available_rect();
         });
         self.egui_winit_state
             .handle_platform_output(window, full_output.platform_output);
-        let clipped_primitives = self
-            .egui_winit_state
-            .egui_ctx()
-            .tessellate(full_output.shapes, full_output.pixels_per_point);
+        let clipped_primitives = {
+            let _span = graphics_context.trace_span("tessellate");
+            self.egui_winit_state
+                .egui_ctx()
+                .tessellate(full_output.shapes, full_output.pixels_per_point)
+        };
         (
             clipped_primitives,
             full_output.textures_delta,
@@ -122,5 +204,40 @@ impl Ui {
         graphics_context: &GraphicsContext,
     ) {
         controller.ui(ctx, ui_state, graphics_context);
+        if ui_state.show_gpu_timings {
+            gpu_timings_window(ctx, ui_state);
+        }
     }
 }
+
+fn gpu_timings_window(ctx: &Context, ui_state: &mut UiState) {
+    let timings = ui_state.gpu_timings;
+    egui::Window::new("GPU timings")
+        .open(&mut ui_state.show_gpu_timings)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let Some(timings) = timings else {
+                ui.label("Timestamp queries are not supported by this adapter");
+                return;
+            };
+            let parts = [
+                ("Compute", timings.compute),
+                ("Shader", timings.shader),
+                ("UI", timings.ui),
+            ];
+            egui::Grid::new("gpu timings").striped(true).show(ui, |ui| {
+                for (name, time) in parts {
+                    ui.label(name);
+                    ui.label(match time {
+                        Some(time) => format!("{:.3} ms", time * 1000.0),
+                        None => "-".to_owned(),
+                    });
+                    ui.end_row();
+                }
+                ui.label("Total");
+                let total: f32 = parts.iter().filter_map(|(_, time)| *time).sum();
+                ui.label(format!("{:.3} ms", total * 1000.0));
+                ui.end_row();
+            });
+        });
+}
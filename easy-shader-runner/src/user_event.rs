@@ -5,4 +5,6 @@ pub enum CustomEvent<C: ControllerTrait> {
     #[cfg(all(feature = "hot-reload-shader", not(target_arch = "wasm32")))]
     NewModule(std::path::PathBuf),
     CreateWindow(Box<Graphics<C>>),
+    /// Setting up the graphics failed, the event loop exits and `run_*` returns the error
+    CreateWindowFailed(crate::Error),
 }
@@ -0,0 +1,29 @@
+/// A vertex or instance buffer (or a sub-range of one) bound to `slot`, matching the layout
+/// at the same index of [`crate::ControllerTrait::describe_vertex_buffer_layouts`]
+pub struct VertexBuffer<'a> {
+    pub slot: u32,
+    pub buffer: wgpu::BufferSlice<'a>,
+}
+
+impl<'a> VertexBuffer<'a> {
+    pub fn new(slot: u32, buffer: wgpu::BufferSlice<'a>) -> Self {
+        Self { slot, buffer }
+    }
+}
+
+/// An index buffer (or a sub-range of one) with `count` indices of `format`
+pub struct IndexBuffer<'a> {
+    pub buffer: wgpu::BufferSlice<'a>,
+    pub format: wgpu::IndexFormat,
+    pub count: u32,
+}
+
+impl<'a> IndexBuffer<'a> {
+    pub fn new(buffer: wgpu::BufferSlice<'a>, format: wgpu::IndexFormat, count: u32) -> Self {
+        Self {
+            buffer,
+            format,
+            count,
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use crate::Options;
-use easy_shader_runner::{ControllerTrait, GraphicsContext, UiState, egui, wgpu, winit};
+use easy_shader_runner::{
+    ComputeBudget, ComputeKernel, ControllerTrait, Dispatch, GpuBuffer, GraphicsContext, UiState,
+    egui, wgpu, winit,
+};
 use glam::*;
 use shared::push_constants::shader::*;
 use shared::*;
 use simulation_runner::SimulationRunner;
-use web_time::Instant;
 use winit::{
     event::{ElementState, KeyEvent, MouseButton},
     keyboard::{Key, NamedKey},
@@ -28,7 +30,6 @@ impl Default for Camera {
 
 pub struct Controller {
     size: UVec2,
-    start: Instant,
     cursor: Vec2,
     mouse_button_pressed: u32,
     camera: Camera,
@@ -36,13 +37,12 @@ pub struct Controller {
     cell_grid: grid::Grid<CellState>,
     transition: bool,
     simulation_runner: SimulationRunner,
-    buffer: Option<wgpu::Buffer>,
+    buffer: Option<GpuBuffer<u32>>,
+    live_cells: Option<usize>,
 }
 
 impl Controller {
     pub fn new(options: &Options) -> Self {
-        let now = Instant::now();
-
         let mut cell_grid = grid::Grid::new(DIM);
         {
             let seed = [
@@ -63,20 +63,23 @@ impl Controller {
 
         Self {
             size: UVec2::ZERO,
-            start: now,
             cursor: Vec2::ZERO,
             mouse_button_pressed: 0,
             camera: Default::default(),
             debug: options.debug,
             cell_grid,
             transition: false,
-            simulation_runner: SimulationRunner::new(now, options.debug),
+            simulation_runner: SimulationRunner::new(options.debug),
             buffer: None,
+            live_cells: None,
         }
     }
 }
 
 impl ControllerTrait for Controller {
+    type PushConstants = FragmentConstants;
+    type ComputeConstants = ComputeConstants;
+
     fn resize(&mut self, size: UVec2) {
         self.size = size;
     }
@@ -129,15 +132,11 @@ impl ControllerTrait for Controller {
         }
     }
 
-    fn prepare_render(
-        &mut self,
-        _gfx_ctx: &GraphicsContext,
-        offset: Vec2,
-    ) -> impl bytemuck::NoUninit {
+    fn prepare_render(&mut self, gfx_ctx: &GraphicsContext, offset: Vec2) -> FragmentConstants {
         FragmentConstants {
             size: self.size.into(),
             translate: offset,
-            time: self.start.elapsed().as_secs_f32(),
+            time: gfx_ctx.clock.elapsed().as_secs_f32(),
             mouse_button_pressed: self.mouse_button_pressed,
             cursor: self.cursor,
             camera_translate: self.camera.translate,
@@ -146,26 +145,38 @@ impl ControllerTrait for Controller {
         }
     }
 
-    fn update<F: Fn(UVec3, UVec3, &[u8])>(
+    fn describe_compute_kernels(&mut self, _gfx_ctx: &GraphicsContext) -> Vec<ComputeKernel> {
+        vec![ComputeKernel::new::<ComputeConstants>("main_cs")]
+    }
+
+    fn update<F: Fn(&str, Dispatch<'_>, &[u8])>(
         &mut self,
-        _gfx_ctx: &GraphicsContext,
+        gfx_ctx: &GraphicsContext,
         compute: F,
-        allowed_duration: f32,
+        budget: ComputeBudget,
     ) {
         let start = web_time::Instant::now();
-        for _ in 0..self.simulation_runner.iterations() {
+        let iterations = self
+            .simulation_runner
+            .iterations(gfx_ctx.clock.delta().as_secs_f32());
+        let iterations = iterations.min(budget.recommended_dispatches().unwrap_or(u32::MAX));
+        for _ in 0..iterations {
             compute(
-                shared::DIM.extend(1),
-                uvec3(16, 16, 1),
+                "main_cs",
+                Dispatch::dimensions_2d(shared::DIM),
                 bytemuck::bytes_of(&ComputeConstants {
                     size: self.size.into(),
-                    time: self.start.elapsed().as_secs_f32(),
+                    time: gfx_ctx.clock.elapsed().as_secs_f32(),
                     zoom: self.camera.zoom,
                     transition: self.transition.into(),
                 }),
             );
             self.transition = !self.transition;
-            if start.elapsed().as_secs_f32() > allowed_duration {
+            // Without GPU timings, fall back to limiting the CPU time spent recording
+            if budget.gpu_time_per_dispatch.is_none()
+                && start.elapsed().as_secs_f32()
+                    > ComputeBudget::COMPUTE_SHARE * budget.allowed_duration
+            {
                 break;
             }
         }
@@ -191,14 +202,12 @@ impl ControllerTrait for Controller {
                 label: Some("cell_grid_layout"),
             });
 
-        use wgpu::util::DeviceExt;
-        let buffer = gfx_ctx
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("cell_grid_buffer"),
-                contents: bytemuck::cast_slice(&self.cell_grid.buffer),
-                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            });
+        let buffer = GpuBuffer::new(
+            &gfx_ctx.device,
+            Some("cell_grid_buffer"),
+            bytemuck::cast_slice(&self.cell_grid.buffer),
+            wgpu::BufferUsages::STORAGE,
+        );
 
         let bind_group = gfx_ctx
             .device
@@ -206,7 +215,7 @@ impl ControllerTrait for Controller {
                 layout: &layout,
                 entries: &[wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: buffer.as_entire_binding(),
+                    resource: buffer.buffer().as_entire_binding(),
                 }],
                 label: Some("cell_grid_bind_group"),
             });
@@ -216,7 +225,7 @@ impl ControllerTrait for Controller {
         (vec![layout], vec![bind_group])
     }
 
-    fn ui(&mut self, ctx: &egui::Context, _ui_state: &mut UiState, gfx_ctx: &GraphicsContext) {
+    fn ui(&mut self, ctx: &egui::Context, ui_state: &mut UiState, gfx_ctx: &GraphicsContext) {
         egui::Window::new("Options")
             .resizable(false)
             .show(ctx, |ui| {
@@ -228,17 +237,39 @@ impl ControllerTrait for Controller {
                 );
                 ui.checkbox(&mut self.simulation_runner.paused, "Paused");
                 ui.checkbox(&mut self.debug, "Debug");
+                ui.checkbox(&mut ui_state.show_gpu_timings, "GPU timings");
+                let entry_points = ui_state.fragment_entry_points().to_vec();
+                if entry_points.len() > 1 {
+                    egui::ComboBox::from_label("Fragment shader")
+                        .selected_text(ui_state.fragment_entry_point.clone())
+                        .show_ui(ui, |ui| {
+                            for entry_point in entry_points {
+                                let text = entry_point.clone();
+                                ui.selectable_value(
+                                    &mut ui_state.fragment_entry_point,
+                                    entry_point,
+                                    text,
+                                );
+                            }
+                        });
+                }
                 if ui.button("Reset").clicked() {
-                    gfx_ctx.queue.write_buffer(
-                        self.buffer.as_ref().unwrap(),
-                        0,
-                        bytemuck::cast_slice(&self.cell_grid.buffer),
-                    );
+                    self.buffer
+                        .as_ref()
+                        .unwrap()
+                        .write(&gfx_ctx.queue, bytemuck::cast_slice(&self.cell_grid.buffer));
                 }
                 if self.debug {
+                    let buffer = self.buffer.as_mut().unwrap();
+                    if let Some(cells) = buffer.try_read(gfx_ctx) {
+                        let live = [CellState::On, CellState::Dying].map(|state| state as u32);
+                        self.live_cells =
+                            Some(cells.iter().filter(|cell| live.contains(cell)).count());
+                    }
+                    buffer.request_read(gfx_ctx);
                     egui::Grid::new("debug_grid").show(ui, |ui| {
                         ui.label("Elapsed");
-                        ui.label(format!("{:.1}s", self.start.elapsed().as_secs_f64()));
+                        ui.label(format!("{:.1}s", gfx_ctx.clock.elapsed().as_secs_f64()));
                         ui.end_row();
 
                         ui.label("Zoom");
@@ -248,6 +279,13 @@ impl ControllerTrait for Controller {
                         ui.label("Translate");
                         ui.label(format!("{:.2}", self.camera.translate));
                         ui.end_row();
+
+                        ui.label("Live cells");
+                        match self.live_cells {
+                            Some(count) => ui.label(count.to_string()),
+                            None => ui.label("-"),
+                        };
+                        ui.end_row();
                     });
                 }
             });
@@ -1,18 +1,14 @@
-use web_time::Instant;
-
 pub struct SimulationRunner {
     pub paused: bool,
     pub speed: f32,
     distance: f32,
-    last_frame: Instant,
 }
 
 impl SimulationRunner {
-    pub fn new(now: Instant, paused: bool) -> Self {
+    pub fn new(paused: bool) -> Self {
         Self {
             speed: 1.0,
             distance: 0.0,
-            last_frame: now,
             paused,
         }
     }
@@ -21,10 +17,10 @@ impl SimulationRunner {
         self.distance += 1.0;
     }
 
-    pub fn iterations(&mut self) -> u32 {
+    /// `delta` is the time in seconds since the previous frame
+    pub fn iterations(&mut self, delta: f32) -> u32 {
         let speed = if self.paused { 0.0 } else { self.speed };
-        let t = self.last_frame.elapsed().as_secs_f32() * 30.0;
-        self.last_frame = Instant::now();
+        let t = delta * 30.0;
         self.distance += speed * t;
         if self.distance >= 1.0 {
             let iterations = self.distance as u32;